@@ -0,0 +1,136 @@
+use clap::Command;
+use std::collections::HashMap;
+
+const MAX_DEPTH: usize = 10;
+
+/// Drops the aliases named like a built-in command, with a warning.
+pub fn valid_aliases(aliases: &HashMap<String, String>, cmd: &Command) -> HashMap<String, String> {
+    aliases
+        .iter()
+        .filter(|(name, _)| {
+            let builtin = is_builtin(cmd, name);
+            if builtin {
+                eprintln!("Alias {name} is ignored, it shadows the built-in command {name}");
+            }
+
+            !builtin
+        })
+        .map(|(name, value)| (name.to_owned(), value.to_owned()))
+        .collect()
+}
+
+/// Replaces the command with its alias before parsing, `$1`, `$2`... being replaced
+/// by the following arguments and `$@` by all of them. Unused arguments are appended.
+pub fn expand_aliases(
+    mut args: Vec<String>,
+    aliases: &HashMap<String, String>,
+    cmd: &Command,
+) -> Result<Vec<String>, String> {
+    let options = options_with_value(cmd);
+    let mut expanded: Vec<String> = vec![];
+
+    loop {
+        let Some(index) = command_index(&args, &options) else {
+            return Ok(args);
+        };
+
+        let name = args[index].clone();
+        let Some(alias) = aliases.get(&name) else {
+            return Ok(args);
+        };
+
+        if expanded.contains(&name) || expanded.len() >= MAX_DEPTH {
+            expanded.push(name);
+            return Err(format!("Alias loop detected: {}", expanded.join(" -> ")));
+        }
+        expanded.push(name.clone());
+
+        let words = shlex::split(alias).ok_or(format!("Invalid alias {name}: {alias}"))?;
+        let rest = args.split_off(index + 1);
+        args.pop();
+        args.extend(substitute(words, rest));
+    }
+}
+
+pub fn aliases_help(aliases: &HashMap<String, String>) -> String {
+    let mut names: Vec<&String> = aliases.keys().collect();
+    names.sort();
+
+    let width = names.iter().map(|name| name.len()).max().unwrap_or(0);
+    let lines: Vec<String> = names
+        .iter()
+        .map(|name| format!("  {name:<width$}  {}", aliases[*name]))
+        .collect();
+
+    format!("Aliases:\n{}", lines.join("\n"))
+}
+
+fn is_builtin(cmd: &Command, name: &str) -> bool {
+    name == "help"
+        || cmd
+            .get_subcommands()
+            .any(|sub| sub.get_name() == name || sub.get_all_aliases().any(|a| a == name))
+}
+
+/// Global options taking a value, skipped when looking for the command.
+fn options_with_value(cmd: &Command) -> Vec<String> {
+    cmd.get_arguments()
+        .filter(|arg| !arg.is_positional() && arg.get_action().takes_values())
+        .flat_map(|arg| {
+            let short = arg.get_short().map(|short| format!("-{short}"));
+            let long = arg.get_long().map(|long| format!("--{long}"));
+            short.into_iter().chain(long)
+        })
+        .collect()
+}
+
+fn command_index(args: &[String], options: &[String]) -> Option<usize> {
+    let mut index = 1;
+
+    while index < args.len() {
+        let arg = &args[index];
+        if !arg.starts_with('-') {
+            return Some(index);
+        }
+
+        if arg == "--" {
+            return None;
+        }
+
+        index += if options.contains(arg) { 2 } else { 1 };
+    }
+
+    None
+}
+
+fn substitute(words: Vec<String>, rest: Vec<String>) -> Vec<String> {
+    let mut used = vec![false; rest.len()];
+    let mut result = vec![];
+
+    for word in words {
+        if word == "$@" {
+            result.extend(rest.iter().cloned());
+            used.iter_mut().for_each(|used| *used = true);
+            continue;
+        }
+
+        let mut word = word;
+        for (i, arg) in rest.iter().enumerate().rev() {
+            let placeholder = format!("${}", i + 1);
+            if word.contains(&placeholder) {
+                word = word.replace(&placeholder, arg);
+                used[i] = true;
+            }
+        }
+        result.push(word);
+    }
+
+    let unused = rest
+        .into_iter()
+        .zip(used)
+        .filter(|(_, used)| !used)
+        .map(|(arg, _)| arg);
+    result.extend(unused);
+
+    result
+}
@@ -1,6 +1,9 @@
 use clap::{Args, Parser, Subcommand};
 use clap_complete::Shell;
 
+use crate::completion::CompleteKind;
+use crate::output::Format;
+
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 #[command(propagate_version = true)]
@@ -11,43 +14,223 @@ pub struct Cli {
 
     #[arg(long = "generate", value_enum)]
     pub generator: Option<Shell>,
+
+    /// Runs the command on the given context (name or id) instead of the active one
+    #[arg(short, long, global = true)]
+    pub context: Option<String>,
+
+    /// Runs clear, rmc and migrate without asking for confirmation
+    #[arg(short, long, global = true)]
+    pub yes: bool,
+
+    /// Shows the changes a command would make instead of saving them
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /// Hides the messages of the commands changing the data, errors being still printed
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+
+    /// Output of ls, lsa, lsc, search and show, json and jsonl also making
+    /// the other commands print the tasks and contexts they changed
+    #[arg(long, global = true, value_enum, default_value_t = Format::Table)]
+    pub format: Format,
 }
 
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     /// uses or creates new context
-    Use(TaskArgs),
-    /// edits task content (takes id of the task then its new content)
+    Use(UseArgs),
+    /// edits task content (takes ids of the tasks then the new content)
     Up(UpdateArgs),
-    /// edits context name (takes id of the new name)
-    Upc(UpdateArgs),
-    /// Shows the list of tasks
-    Ls,
+    /// edits context name (takes name or id of the context then its new name)
+    Upc(UpdateContextArgs),
+    /// Shows the list of tasks, optionally filtered (e.g. 'status:open and tag:bug')
+    Ls(ListArgs),
     /// Shows the list of contexts
-    Lsc,
-    /// shows the list of all tasks from all contexts
-    Lsa,
+    Lsc(ListContextsArgs),
+    /// shows the list of all tasks from all contexts, optionally filtered
+    Lsa(ListArgs),
+    /// Searches tasks content across all contexts (case-insensitive)
+    Search(SearchArgs),
+    /// Shows every field of a task
+    Show(IdArgs),
     /// Created task based on content string
-    Add(TaskArgs),
-    /// Marks one or several tasks (separated by a comma) as done
-    Done(TaskArgs),
-    /// Deletes one or several tasks (separated by a comma) based on the id
-    Rm(TaskArgs),
+    Add(AddArgs),
+    /// Marks one or several tasks as done (ids like 1,3-4,9, all, done or ^3 to exclude one)
+    Done(SelectArgs),
+    /// Applies changes to the selected tasks: content:text, /old/new/, status:done|open,
+    /// +tag, -tag, tags:a,b, priority:H or due:friday (empty value to reset)
+    Modify(ModifyArgs),
+    /// Moves a task one position up
+    Mvup(IdArgs),
+    /// Moves a task one position down
+    Mvdown(IdArgs),
+    /// Moves a task to the given position (takes id of the task then its new position)
+    Order(OrderArgs),
+    /// Deletes one or several tasks (ids like 1,3-4,9, all, done or ^3 to exclude one)
+    Rm(SelectArgs),
     /// deletes one or several contexts (separated by a comma) based on the name    
     Rmc(TaskArgs),
+    /// Sets context metadata: description, color, priority or tags (default ones for new tasks)
+    Setc(SetContextArgs),
+    /// Archives a context, hiding it from lsc and lsa
+    Archivec(TaskArgs),
+    /// Restores an archived context
+    Unarchivec(TaskArgs),
+    /// Shows a report defined in the config, also available as task {name}
+    Report(ReportArgs),
+    /// Shows the list of reports defined in the config
+    Reports,
+    /// Edits the tasks of a context (the active one by default) as a checklist in $EDITOR
+    Edit(EditArgs),
+    /// Opens a full-screen interface to browse and edit the tasks
+    Tui,
+    /// Shows the activity log, of every context unless --context is given
+    Log(LogArgs),
+    /// Reverts the last changes (one by default)
+    Undo(StepsArgs),
+    /// Applies again the last undone changes
+    Redo(StepsArgs),
     /// Clear all tasks for the active context
     Clear,
     /// Migrate your data from file to rest API
     Migrate,
+    /// Prints the values to complete, used by the shell completion scripts
+    #[command(hide = true)]
+    Complete(CompleteArgs),
+    #[command(external_subcommand)]
+    External(Vec<String>),
 }
 
 #[derive(Args, Debug)]
 pub struct UpdateArgs {
-    pub id: usize,
+    pub id: Option<String>,
+    pub name: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct UpdateContextArgs {
     pub name: String,
+    pub new_name: String,
 }
 
 #[derive(Args, Debug)]
 pub struct TaskArgs {
     pub name: String,
 }
+
+#[derive(Args, Debug)]
+pub struct AddArgs {
+    /// Content of the task, or - to read one task per line from stdin
+    #[arg(required_unless_present = "file")]
+    pub name: Option<String>,
+    /// Reads one task per line from this file
+    #[arg(short, long, conflicts_with = "name")]
+    pub file: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct CompleteArgs {
+    #[arg(value_enum)]
+    pub kind: CompleteKind,
+}
+
+#[derive(Args, Debug)]
+pub struct EditArgs {
+    pub context: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct UseArgs {
+    pub name: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct LogArgs {
+    /// Only shows the events since a date (YYYY-MM-DD, yesterday, monday) or a duration (3h, 7d, 2w)
+    #[arg(short, long)]
+    pub since: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct StepsArgs {
+    #[arg(default_value_t = 1)]
+    pub steps: usize,
+}
+
+#[derive(Args, Debug)]
+pub struct IdArgs {
+    pub id: usize,
+}
+
+#[derive(Args, Debug)]
+pub struct OrderArgs {
+    pub id: usize,
+    pub position: usize,
+}
+
+#[derive(Args, Debug)]
+pub struct SetContextArgs {
+    pub name: String,
+    pub key: String,
+    /// New value, leave empty to reset the key
+    #[arg(default_value = "")]
+    pub value: String,
+}
+
+#[derive(Args, Debug)]
+pub struct SelectArgs {
+    pub name: Option<String>,
+    /// Only selects the tasks matching this filter
+    #[arg(short = 'w', long = "where")]
+    pub filter: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct ModifyArgs {
+    pub name: String,
+    #[arg(required = true, allow_hyphen_values = true)]
+    pub changes: Vec<String>,
+    /// Only modifies the selected tasks matching this filter
+    #[arg(short = 'w', long = "where")]
+    pub filter: Option<String>,
+}
+
+#[derive(Args, Debug, Default)]
+pub struct ListArgs {
+    /// Filter expression, e.g. 'status:open and (tag:bug or priority:H) and due.before:friday'
+    pub filter: Vec<String>,
+    /// Sorts by id (manual order), created, modified, status, content, due or priority,
+    /// add :desc for descending order (e.g. due:desc)
+    #[arg(short, long)]
+    pub sort: Option<String>,
+    /// Splits the list in sections by status, context or tag
+    #[arg(short, long)]
+    pub group_by: Option<String>,
+    /// Keeps the list on screen and redraws it when the tasks change
+    #[arg(short, long)]
+    pub watch: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct SearchArgs {
+    pub text: String,
+    /// Treats the text as a regular expression
+    #[arg(short, long)]
+    pub regex: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct ReportArgs {
+    pub name: String,
+    /// Filter expression added to the report one
+    pub filter: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct ListContextsArgs {
+    /// Includes archived contexts
+    #[arg(short, long)]
+    pub all: bool,
+}
@@ -0,0 +1,254 @@
+use clap::ValueEnum;
+use clap_complete::Shell;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::structs::{Config, Service};
+use crate::utils::{format_task, get_context_index};
+
+/// Seconds during which the fetched values are reused, so SSH and API lookups don't stall the shell
+const CACHE_SECONDS: u64 = 30;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum CompleteKind {
+    /// Contexts that are not archived
+    Contexts,
+    /// Tasks of the context
+    Tasks,
+    /// Open tasks of the context
+    Open,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Candidate {
+    value: String,
+    description: String,
+    done: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Cache {
+    key: String,
+    time: u64,
+    contexts: Vec<Candidate>,
+    tasks: Vec<Candidate>,
+}
+
+/// Prints the values to complete as `value<TAB>description` lines, used by the shell scripts.
+pub fn print_candidates(config: &Config, service: &impl Service, kind: CompleteKind) {
+    let Some(cache) = cached(config).or_else(|| fetch(config, service)) else {
+        return;
+    };
+
+    let candidates = match kind {
+        CompleteKind::Contexts => cache.contexts,
+        CompleteKind::Tasks => cache.tasks,
+        CompleteKind::Open => cache.tasks.into_iter().filter(|task| !task.done).collect(),
+    };
+
+    for candidate in candidates {
+        println!("{}\t{}", candidate.value, candidate.description);
+    }
+}
+
+/// Drops the cached values once the data changed.
+pub fn clear_cache() {
+    if let Some(path) = cache_path() {
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// Script completing context names and task ids, added after the static completions.
+pub fn dynamic_script(shell: Shell) -> Option<&'static str> {
+    match shell {
+        Shell::Bash => Some(BASH_SCRIPT),
+        Shell::Zsh => Some(ZSH_SCRIPT),
+        Shell::Fish => Some(FISH_SCRIPT),
+        _ => None,
+    }
+}
+
+fn cached(config: &Config) -> Option<Cache> {
+    let text = fs::read_to_string(cache_path()?).ok()?;
+    let cache: Cache = serde_json::from_str(&text).ok()?;
+
+    (cache.key == cache_key(config) && now().saturating_sub(cache.time) < CACHE_SECONDS)
+        .then_some(cache)
+}
+
+fn fetch(config: &Config, service: &impl Service) -> Option<Cache> {
+    let data = service.read_contexts(config).ok()?;
+
+    let contexts = data
+        .iter()
+        .filter(|ctx| !ctx.archived)
+        .map(|ctx| Candidate {
+            value: ctx.name.clone(),
+            description: format!("{} tasks", ctx.tasks.len()),
+            done: false,
+        })
+        .collect();
+
+    let tasks = match get_context_index(&data, config) {
+        Ok(index) => data[index]
+            .tasks
+            .iter()
+            .enumerate()
+            .map(|(position, task)| Candidate {
+                value: (position + 1).to_string(),
+                description: format_task(task),
+                done: task.done,
+            })
+            .collect(),
+        Err(_) => vec![],
+    };
+
+    let cache = Cache {
+        key: cache_key(config),
+        time: now(),
+        contexts,
+        tasks,
+    };
+
+    if let Some(path) = cache_path() {
+        let json = serde_json::to_string(&cache).expect("Error when stringifying cache");
+        let _ = path
+            .parent()
+            .map(fs::create_dir_all)
+            .and_then(|_| fs::write(&path, json).ok());
+    }
+
+    Some(cache)
+}
+
+/// The cache is only valid for the same storage and context.
+fn cache_key(config: &Config) -> String {
+    format!(
+        "{}|{}|{}|{}|{}",
+        config.api_url,
+        config.ssh_ip,
+        config.ssh_file_path,
+        config.local_file_path,
+        config.context.clone().unwrap_or_default()
+    )
+}
+
+fn cache_path() -> Option<PathBuf> {
+    let folder = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+
+    Some(folder.join("tasks").join("completion.json"))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+const BASH_SCRIPT: &str = r#"
+_task_dynamic() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" sub="" count=0 skip=0 kind="" word
+    for word in "${COMP_WORDS[@]:1:COMP_CWORD-1}"; do
+        if [[ $skip -eq 1 ]]; then skip=0; continue; fi
+        case "$word" in
+            -c|--context) skip=1 ;;
+            -*) ;;
+            *) if [[ -z $sub ]]; then sub="$word"; else count=$((count + 1)); fi ;;
+        esac
+    done
+
+    case "$sub" in
+        use|rmc|upc|setc|archivec|edit) kind=contexts ;;
+        done) kind=open ;;
+        rm|up|modify|show) kind=tasks ;;
+    esac
+
+    if [[ -n $kind && $count -eq 0 && $cur != -* ]]; then
+        local IFS=$'\n'
+        COMPREPLY=($(compgen -W "$(task complete "$kind" 2>/dev/null | cut -f1)" -- "$cur"))
+        return
+    fi
+
+    _task "$@"
+}
+
+complete -F _task_dynamic -o bashdefault -o default task
+"#;
+
+const ZSH_SCRIPT: &str = r#"
+_task_dynamic() {
+    local sub="" kind="" word
+    local -i count=0 skip=0
+    for word in "${(@)words[2,CURRENT-1]}"; do
+        if (( skip )); then skip=0; continue; fi
+        case "$word" in
+            -c|--context) skip=1 ;;
+            -*) ;;
+            *) if [[ -z $sub ]]; then sub="$word"; else (( count++ )); fi ;;
+        esac
+    done
+
+    case "$sub" in
+        use|rmc|upc|setc|archivec|edit) kind=contexts ;;
+        done) kind=open ;;
+        rm|up|modify|show) kind=tasks ;;
+    esac
+
+    if [[ -n $kind && $count -eq 0 && $PREFIX != -* ]]; then
+        local -a values
+        values=("${(@f)$(task complete "$kind" 2>/dev/null | sed -e 's/:/\\:/g' -e 's/\t/:/')}")
+        _describe -t values "$kind" values
+        return
+    fi
+
+    _task "$@"
+}
+
+compdef _task_dynamic task
+"#;
+
+const FISH_SCRIPT: &str = r#"
+function __task_dynamic_kind
+    set -l tokens (commandline -opc)
+    set -e tokens[1]
+    set -l sub
+    set -l count 0
+    set -l skip 0
+    for token in $tokens
+        if test $skip -eq 1
+            set skip 0
+            continue
+        end
+        switch $token
+            case -c --context
+                set skip 1
+            case '-*'
+            case '*'
+                if test -z "$sub"
+                    set sub $token
+                else
+                    set count (math $count + 1)
+                end
+        end
+    end
+
+    test $count -eq 0; or return 1
+    switch $sub
+        case use rmc upc setc archivec edit
+            echo contexts
+        case done
+            echo open
+        case rm up modify show
+            echo tasks
+        case '*'
+            return 1
+    end
+end
+
+complete -c task -n __task_dynamic_kind -f -a '(task complete (__task_dynamic_kind) 2>/dev/null)'
+"#;
@@ -0,0 +1,67 @@
+use crate::errors::{report_as, Error, TaskError};
+use crate::picker::{is_interactive, prompt};
+use crate::structs::{Config, Service};
+use crate::utils::get_context_index;
+
+/// Asks before a destructive command, showing what it destroys. Skipped with `--yes`
+/// and `--dry-run`, and refused when nobody can answer.
+pub fn confirm(config: &Config, command: &str, summary: &str) -> bool {
+    if config.yes || config.dry_run {
+        return true;
+    }
+
+    if !is_interactive() {
+        eprintln!("{summary}");
+        report_as(
+            TaskError::InvalidInput,
+            format!("Not running {command} without confirmation, add --yes to confirm"),
+        );
+        return false;
+    }
+
+    eprintln!("{summary}");
+    let confirmed = prompt("Continue? [y/N] ")
+        .is_some_and(|answer| matches!(answer.to_lowercase().as_str(), "y" | "yes"));
+    if !confirmed {
+        eprintln!("Cancelled");
+    }
+
+    confirmed
+}
+
+/// What `clear` deletes: the tasks of the context.
+pub fn clear_summary(config: &Config, service: &impl Service) -> Result<String, Error> {
+    let data = service.get_contexts(config)?;
+    let ctx = &data[get_context_index(&data, config)?];
+
+    Ok(format!(
+        "This deletes {} of context {}",
+        count_tasks(ctx.tasks.len()),
+        ctx.name
+    ))
+}
+
+/// What `rmc` deletes: the contexts and their tasks, found like the command finds them.
+pub fn rmc_summary(config: &Config, service: &impl Service, args: &str) -> Result<String, Error> {
+    let contexts = service.find_contexts(config, args)?;
+
+    if contexts.is_empty() {
+        return Err(Error::not_found(format!(
+            "No context found with this name or ID: {args}"
+        )));
+    }
+
+    let names: Vec<String> = contexts
+        .iter()
+        .map(|ctx| format!("{} ({})", ctx.name, count_tasks(ctx.tasks)))
+        .collect();
+
+    Ok(format!("This deletes the context {}", names.join(", ")))
+}
+
+pub fn count_tasks(count: usize) -> String {
+    match count {
+        1 => String::from("1 task"),
+        count => format!("{count} tasks"),
+    }
+}
@@ -0,0 +1,270 @@
+use chrono::Local;
+use regex::Regex;
+use std::collections::HashSet;
+use std::fs::{DirBuilder, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::PathBuf;
+use std::process::Command;
+use std::{env, fs, process};
+
+use crate::errors::{report, Error};
+use crate::modify::NewTask;
+use crate::services::file::FileService;
+use crate::structs::{Config, Context, Service, Task};
+use crate::utils::{get_context_index, print_message};
+
+const HELP: &str = "\
+# Save and quit to apply the changes, lines starting with # are ignored.
+# Removing a line deletes the task, a new line creates one (+tag, priority:H and due:friday are allowed).
+# Keep the <!-- #id --> markers so renamed and moved tasks keep their tags, priority and due date.
+# Save an empty file to cancel.
+";
+
+/// Opens the tasks of the context as a Markdown checklist in $EDITOR, then applies
+/// the added, removed, moved, renamed and toggled lines.
+pub fn edit_context(config: &Config, service: &FileService, name: Option<String>) {
+    let mut config = config.clone();
+    if name.is_some() {
+        config.context = name;
+    }
+
+    let ctx = match service.get_contexts(&config).and_then(|data| {
+        let index = get_context_index(&data, &config)?;
+        Ok(data[index].clone())
+    }) {
+        Ok(ctx) => ctx,
+        Err(err) => {
+            report(err);
+            return;
+        }
+    };
+    config.context = Some(ctx.name.clone());
+
+    let mut text = render(&ctx);
+    let tasks = loop {
+        let edited = match open_editor(&text) {
+            Ok(edited) => edited,
+            Err(err) => {
+                report(err);
+                return;
+            }
+        };
+
+        if edited.trim().is_empty() {
+            print_message(&config, "Edit cancelled");
+            return;
+        }
+
+        match parse(&edited, &ctx) {
+            Ok(tasks) => break tasks,
+            // Reopens the editor with the edits and the error on top
+            Err(err) => {
+                let lines: Vec<&str> = edited
+                    .lines()
+                    .filter(|line| !line.starts_with("# Error: "))
+                    .collect();
+                text = format!("# Error: {err}\n{}\n", lines.join("\n"));
+            }
+        }
+    };
+
+    let changes = describe_changes(&ctx.tasks, &tasks);
+    if changes.is_empty() {
+        print_message(&config, "No changes");
+        return;
+    }
+
+    let date = Local::now().to_string();
+    let tasks = tasks
+        .into_iter()
+        .enumerate()
+        .map(|(index, (_, mut task))| {
+            if task.id != index + 1 {
+                task.id = index + 1;
+                task.modification_date = date.clone();
+            }
+            task
+        })
+        .collect();
+    service.set_tasks(&config, tasks);
+    if !config.dry_run {
+        let message = format!("Context {} updated: {}", ctx.name, changes.join(", "));
+        print_message(&config, message);
+    }
+}
+
+fn render(ctx: &Context) -> String {
+    let lines: Vec<String> = ctx
+        .tasks
+        .iter()
+        .map(|task| {
+            let status = if task.done { "x" } else { " " };
+            format!("- [{status}] {} <!-- #{} -->", task.content, task.id)
+        })
+        .collect();
+
+    format!("# Context: {}\n{HELP}\n{}\n", ctx.name, lines.join("\n"))
+}
+
+/// Builds the new task list with the original id of each task, the tasks
+/// with a marker keeping their metadata.
+fn parse(text: &str, ctx: &Context) -> Result<Vec<(Option<usize>, Task)>, String> {
+    let item = Regex::new(r"^[-*] \[([ xX])\]\s*(.*)$").unwrap();
+    let marker = Regex::new(r"\s*<!--\s*#(\d+)\s*-->\s*$").unwrap();
+    let date = Local::now().to_string();
+
+    let mut seen = HashSet::new();
+    let mut tasks = vec![];
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let captures = item
+            .captures(line)
+            .ok_or(format!("expected - [ ] or - [x] at the start of: {line}"))?;
+        let done = &captures[1] != " ";
+        let rest = &captures[2];
+
+        let (content, id) = match marker.captures(rest) {
+            Some(found) => (
+                &rest[..found.get(0).unwrap().start()],
+                found.get(1).map(|id| id.as_str()),
+            ),
+            None => (rest, None),
+        };
+        if content.trim().is_empty() {
+            return Err(format!("the content can't be empty: {line}"));
+        }
+
+        let mut task = match id {
+            Some(id) => {
+                let original = ctx
+                    .tasks
+                    .iter()
+                    .find(|task| task.id.to_string() == id)
+                    .ok_or(format!("unknown task #{id}: {line}"))?;
+                if !seen.insert(original.id) {
+                    return Err(format!("task #{id} appears twice"));
+                }
+
+                let mut task = original.clone();
+                if task.content != content.trim() || task.done != done {
+                    task.content = content.trim().to_owned();
+                    task.modification_date = date.clone();
+                }
+                task
+            }
+            None => NewTask::parse(content)?.to_task(ctx, 0),
+        };
+
+        task.done = done;
+        tasks.push((id.is_some().then_some(task.id), task));
+    }
+
+    Ok(tasks)
+}
+
+fn describe_changes(before: &[Task], after: &[(Option<usize>, Task)]) -> Vec<String> {
+    let kept: Vec<(&Task, &Task)> = after
+        .iter()
+        .filter_map(|(id, task)| {
+            let old = before.iter().find(|old| Some(old.id) == *id)?;
+            Some((old, task))
+        })
+        .collect();
+
+    let added = after.len() - kept.len();
+    let removed = before.len() - kept.len();
+    let renamed = kept
+        .iter()
+        .filter(|(old, new)| old.content != new.content)
+        .count();
+    let toggled = kept
+        .iter()
+        .filter(|(old, new)| old.done != new.done)
+        .count();
+    let reordered = kept.windows(2).any(|pair| pair[0].0.id > pair[1].0.id);
+
+    let mut changes = vec![];
+    for (count, label) in [
+        (added, "added"),
+        (removed, "removed"),
+        (renamed, "renamed"),
+        (toggled, "toggled"),
+    ] {
+        if count > 0 {
+            changes.push(format!("{count} {label}"));
+        }
+    }
+    if reordered {
+        changes.push(String::from("reordered"));
+    }
+
+    changes
+}
+
+/// Directory only readable by the user holding the edited file, removed when dropped.
+struct EditDir(PathBuf);
+
+impl EditDir {
+    fn create() -> Result<Self, Error> {
+        let nanos = Local::now().timestamp_subsec_nanos();
+        let path = env::temp_dir().join(format!("task-edit-{}-{nanos}", process::id()));
+        DirBuilder::new().mode(0o700).create(&path).map_err(|err| {
+            Error::storage(format!("Error when creating {}: {err}", path.display()))
+        })?;
+
+        Ok(Self(path))
+    }
+}
+
+impl Drop for EditDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Writes the text in a temporary file opened with $VISUAL or $EDITOR (vi by default).
+fn open_editor(text: &str) -> Result<String, Error> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or(String::from("vi"));
+    let words = shlex::split(&editor)
+        .filter(|words| !words.is_empty())
+        .ok_or(Error::invalid(format!("Invalid editor: {editor}")))?;
+
+    let dir = EditDir::create()?;
+    let path = dir.0.join("tasks.md");
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&path)
+        .and_then(|mut file| file.write_all(text.as_bytes()))
+        .map_err(|err| Error::storage(format!("Error when writing {}: {err}", path.display())))?;
+
+    let mut command = Command::new(&words[0]);
+    command.args(&words[1..]).arg(&path);
+    // The editor draws on the terminal even when the output is captured for --quiet or --format
+    if let Ok(terminal) = OpenOptions::new().write(true).open("/dev/tty") {
+        command.stdout(terminal);
+    }
+
+    let status = command
+        .status()
+        .map_err(|err| Error::storage(format!("Error when running {editor}: {err}")));
+
+    let edited = fs::read_to_string(&path)
+        .map_err(|err| Error::storage(format!("Error when reading {}: {err}", path.display())));
+
+    match status? {
+        status if status.success() => edited,
+        status => Err(Error::storage(format!(
+            "{editor} failed ({status}), no changes applied"
+        ))),
+    }
+}
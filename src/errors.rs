@@ -0,0 +1,78 @@
+use std::fmt::{self, Display};
+use std::sync::atomic::{AtomicI32, Ordering};
+
+/// Exit code of the process, set by the first error reported.
+static EXIT_CODE: AtomicI32 = AtomicI32::new(0);
+
+/// Kinds of failures, each one exiting with its own code.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TaskError {
+    /// Invalid arguments, ids, filters or changes, the code of clap usage errors
+    InvalidInput,
+    /// No such context, task or report, or no active context
+    NotFound,
+    /// The data file, SSH, API, editor or plugin failed
+    Storage,
+    /// The change clashes with the stored data
+    Conflict,
+}
+
+impl TaskError {
+    pub fn code(self) -> i32 {
+        match self {
+            TaskError::InvalidInput => 2,
+            TaskError::NotFound => 3,
+            TaskError::Storage => 4,
+            TaskError::Conflict => 5,
+        }
+    }
+}
+
+/// An error message with its kind, returned by the functions failing in several ways.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    pub kind: TaskError,
+    pub message: String,
+}
+
+impl Error {
+    pub fn new(kind: TaskError, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    pub fn invalid(message: impl Into<String>) -> Self {
+        Self::new(TaskError::InvalidInput, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(TaskError::NotFound, message)
+    }
+
+    pub fn storage(message: impl Into<String>) -> Self {
+        Self::new(TaskError::Storage, message)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Prints the error on stderr and keeps the exit code of its kind.
+pub fn report(err: Error) {
+    report_as(err.kind, err.message);
+}
+
+/// Prints the error on stderr and keeps its exit code, unless an earlier error set one.
+pub fn report_as(kind: TaskError, message: impl Display) {
+    eprintln!("{message}");
+    let _ = EXIT_CODE.compare_exchange(0, kind.code(), Ordering::SeqCst, Ordering::SeqCst);
+}
+
+pub fn exit_code() -> i32 {
+    EXIT_CODE.load(Ordering::SeqCst)
+}
@@ -0,0 +1,431 @@
+use chrono::NaiveDate;
+
+use crate::structs::{Context, Priority, Task};
+use crate::utils::{parse_date, task_date};
+
+/// Filter expression used by listings and bulk commands, e.g.
+/// `status:open and (tag:bug or priority:H) and due.before:friday`.
+/// It is evaluated on the loaded tasks, the API getting no part of it.
+#[derive(Debug, Clone)]
+pub enum Filter {
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Term(Term),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cmp {
+    Eq,
+    Lt,
+    Gt,
+}
+
+#[derive(Debug, Clone)]
+pub enum Term {
+    Status(bool),
+    Tag(String),
+    TagContains(String),
+    Priority(Cmp, Option<Priority>),
+    Due(Cmp, Option<NaiveDate>),
+    Created(Cmp, NaiveDate),
+    Modified(Cmp, NaiveDate),
+    Content(String),
+    ContentContains(String),
+    Context(String),
+    ContextContains(String),
+    Id(usize),
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Term(String),
+}
+
+impl Filter {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let tokens = tokenize(input)?;
+        if tokens.is_empty() {
+            return Err(String::from("Empty filter"));
+        }
+
+        let mut parser = Parser { tokens, pos: 0 };
+        let filter = parser.parse_or()?;
+
+        if parser.pos < parser.tokens.len() {
+            return Err(format!(
+                "Unexpected token in filter: {:?}",
+                parser.tokens[parser.pos]
+            ));
+        }
+
+        Ok(filter)
+    }
+
+    pub fn matches(&self, task: &Task, ctx: &Context) -> bool {
+        match self {
+            Filter::And(left, right) => left.matches(task, ctx) && right.matches(task, ctx),
+            Filter::Or(left, right) => left.matches(task, ctx) || right.matches(task, ctx),
+            Filter::Not(filter) => !filter.matches(task, ctx),
+            Filter::Term(term) => term.matches(task, ctx),
+        }
+    }
+}
+
+impl Term {
+    fn parse(input: &str) -> Result<Self, String> {
+        let (key, contains, value) = match input.find([':', '~']) {
+            Some(pos) => (
+                &input[..pos],
+                input[pos..].starts_with('~'),
+                unquote(&input[pos + 1..]),
+            ),
+            None => {
+                return Err(format!(
+                    "Invalid filter term: {input}, expected field:value"
+                ))
+            }
+        };
+
+        let (field, modifier) = match key.split_once('.') {
+            Some((field, modifier)) => (field, Some(modifier)),
+            None => (key, None),
+        };
+
+        let cmp = match modifier {
+            None | Some("is") | Some("eq") => Cmp::Eq,
+            Some("before") | Some("below") | Some("lt") => Cmp::Lt,
+            Some("after") | Some("above") | Some("gt") => Cmp::Gt,
+            Some(modifier) => return Err(format!("Unknown filter modifier: {modifier}")),
+        };
+
+        if contains && cmp != Cmp::Eq {
+            return Err(format!("Modifiers can't be used with ~: {input}"));
+        }
+
+        let term = match (field, contains) {
+            ("status", false) => match value.to_lowercase().as_str() {
+                "open" | "pending" | "todo" => Term::Status(false),
+                "done" | "completed" => Term::Status(true),
+                _ => return Err(format!("Invalid status: {value}, use open or done")),
+            },
+            ("tag" | "tags", false) => Term::Tag(value.trim_start_matches('+').to_lowercase()),
+            ("tag" | "tags", true) => Term::TagContains(value.to_lowercase()),
+            ("priority" | "pri", false) => {
+                let priority = match value.to_lowercase().as_str() {
+                    "" | "none" => None,
+                    _ => Some(value.parse()?),
+                };
+                Term::Priority(cmp, priority)
+            }
+            ("due", false) => {
+                let date = match value.to_lowercase().as_str() {
+                    "" | "none" => None,
+                    _ => Some(parse_date(&value)?),
+                };
+                Term::Due(cmp, date)
+            }
+            ("created", false) => Term::Created(cmp, parse_date(&value)?),
+            ("modified", false) => Term::Modified(cmp, parse_date(&value)?),
+            ("content", false) => Term::Content(value.to_lowercase()),
+            ("content", true) => Term::ContentContains(value.to_lowercase()),
+            ("context" | "ctx", false) => Term::Context(value),
+            ("context" | "ctx", true) => Term::ContextContains(value.to_lowercase()),
+            ("id", false) => Term::Id(
+                value
+                    .parse()
+                    .map_err(|_| format!("Invalid id in filter: {value}"))?,
+            ),
+            (field, true) => return Err(format!("~ can't be used with field: {field}")),
+            (field, false) => return Err(format!("Unknown filter field: {field}")),
+        };
+
+        let comparable = matches!(
+            term,
+            Term::Priority(..) | Term::Due(..) | Term::Created(..) | Term::Modified(..)
+        );
+        if cmp != Cmp::Eq && !comparable {
+            return Err(format!("Modifiers can't be used with field: {field}"));
+        }
+
+        Ok(term)
+    }
+
+    fn matches(&self, task: &Task, ctx: &Context) -> bool {
+        match self {
+            Term::Status(done) => task.done == *done,
+            Term::Tag(tag) if tag.is_empty() => task.tags.is_empty(),
+            Term::Tag(tag) => task.tags.iter().any(|t| t.to_lowercase() == *tag),
+            Term::TagContains(text) => task.tags.iter().any(|t| t.to_lowercase().contains(text)),
+            Term::Priority(cmp, priority) => match (cmp, priority) {
+                (Cmp::Eq, _) => task.priority == *priority,
+                (_, None) => false,
+                (cmp, Some(priority)) => task
+                    .priority
+                    .is_some_and(|task_priority| compare(*cmp, task_priority, *priority)),
+            },
+            Term::Due(cmp, date) => {
+                let due = task.due.as_deref().and_then(task_date);
+                match (cmp, date) {
+                    (Cmp::Eq, _) => due == *date,
+                    (_, None) => false,
+                    (cmp, Some(date)) => due.is_some_and(|due| compare(*cmp, due, *date)),
+                }
+            }
+            Term::Created(cmp, date) => {
+                task_date(&task.creation_date).is_some_and(|created| compare(*cmp, created, *date))
+            }
+            Term::Modified(cmp, date) => task_date(&task.modification_date)
+                .is_some_and(|modified| compare(*cmp, modified, *date)),
+            Term::Content(text) => task.content.to_lowercase() == *text,
+            Term::ContentContains(text) => task.content.to_lowercase().contains(text),
+            Term::Context(name) => ctx.name == *name || ctx.id.to_string() == *name,
+            Term::ContextContains(text) => ctx.name.to_lowercase().contains(text),
+            Term::Id(id) => task.id == *id,
+        }
+    }
+}
+
+fn compare<T: PartialOrd>(cmp: Cmp, left: T, right: T) -> bool {
+    match cmp {
+        Cmp::Eq => left == right,
+        Cmp::Lt => left < right,
+        Cmp::Gt => left > right,
+    }
+}
+
+fn unquote(value: &str) -> String {
+    let trimmed = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')));
+
+    trimmed.unwrap_or(value).to_owned()
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            ' ' | '\t' | '\n' => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '!' => {
+                chars.next();
+                tokens.push(Token::Not);
+            }
+            _ => {
+                let mut word = String::new();
+                let mut quote: Option<char> = None;
+
+                while let Some(&c) = chars.peek() {
+                    match quote {
+                        Some(q) if c == q => quote = None,
+                        Some(_) => {}
+                        None if c == '"' || c == '\'' => quote = Some(c),
+                        None if c.is_whitespace() || c == '(' || c == ')' => break,
+                        None => {}
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+
+                if quote.is_some() {
+                    return Err(format!("Unclosed quote in filter: {word}"));
+                }
+
+                let token = match word.to_lowercase().as_str() {
+                    "and" | "&&" => Token::And,
+                    "or" | "||" => Token::Or,
+                    "not" => Token::Not,
+                    _ => Token::Term(word),
+                };
+                tokens.push(token);
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn parse_or(&mut self) -> Result<Filter, String> {
+        let mut left = self.parse_and()?;
+
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            let right = self.parse_and()?;
+            left = Filter::Or(Box::new(left), Box::new(right));
+        }
+
+        Ok(left)
+    }
+
+    /// Terms next to each other without operator are joined with `and`.
+    fn parse_and(&mut self) -> Result<Filter, String> {
+        let mut left = self.parse_not()?;
+
+        loop {
+            match self.peek() {
+                Some(Token::And) => self.pos += 1,
+                Some(Token::Term(_)) | Some(Token::Open) | Some(Token::Not) => {}
+                _ => break,
+            }
+
+            let right = self.parse_not()?;
+            left = Filter::And(Box::new(left), Box::new(right));
+        }
+
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Filter, String> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            return Ok(Filter::Not(Box::new(self.parse_not()?)));
+        }
+
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Filter, String> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Open) => {
+                self.pos += 1;
+                let filter = self.parse_or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err(String::from("Missing closing parenthesis in filter"));
+                }
+                self.pos += 1;
+                Ok(filter)
+            }
+            Some(Token::Term(term)) => {
+                let term = Term::parse(term)?;
+                self.pos += 1;
+                Ok(Filter::Term(term))
+            }
+            Some(token) => Err(format!("Unexpected token in filter: {:?}", token)),
+            None => Err(String::from("Unexpected end of filter")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(content: &str, done: bool, tags: &[&str], priority: Option<Priority>) -> Task {
+        Task {
+            id: 1,
+            content: content.to_owned(),
+            done,
+            creation_date: String::from("2026-10-10 09:00:00.000000000 +00:00"),
+            modification_date: String::from("2026-10-12 09:00:00.000000000 +00:00"),
+            priority,
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            due: Some(String::from("2026-10-15")),
+        }
+    }
+
+    fn matches(filter: &str, task: &Task) -> bool {
+        let ctx = Context::new("work", 1);
+        Filter::parse(filter).unwrap().matches(task, &ctx)
+    }
+
+    fn error(filter: &str) -> String {
+        Filter::parse(filter).unwrap_err()
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let open_bug = task("Fix login", false, &["bug"], None);
+
+        assert!(matches("tag:bug or tag:docs and status:done", &open_bug));
+        assert!(!matches("(tag:bug or tag:docs) and status:done", &open_bug));
+    }
+
+    #[test]
+    fn not_applies_to_the_next_term() {
+        let both = task("Fix docs", false, &["bug", "docs"], None);
+        let bug = task("Fix login", false, &["bug"], None);
+
+        assert!(matches("not tag:bug or tag:docs", &both));
+        assert!(!matches("not tag:bug or tag:docs", &bug));
+        assert!(!matches("!tag:bug", &bug));
+        assert!(matches("not (tag:bug and tag:docs)", &bug));
+    }
+
+    #[test]
+    fn terms_without_operator_are_joined_with_and() {
+        let bug = task("Fix login", false, &["bug"], Some(Priority::H));
+
+        assert!(matches("tag:bug status:open", &bug));
+        assert!(!matches("tag:bug status:done", &bug));
+        assert!(matches("tag:bug (priority:H or priority:M)", &bug));
+    }
+
+    #[test]
+    fn comparisons_use_the_dates_and_the_priority_order() {
+        let bug = task("Fix login", false, &["bug"], Some(Priority::M));
+
+        assert!(matches("due.before:2026-10-16", &bug));
+        assert!(!matches("due.after:2026-10-15", &bug));
+        assert!(matches(
+            "created.after:2026-10-09 modified.before:2026-10-13",
+            &bug
+        ));
+        assert!(matches("priority.above:L priority.below:H", &bug));
+        assert!(!matches("priority.above:M", &bug));
+        assert!(!matches("due:none", &bug));
+    }
+
+    #[test]
+    fn invalid_filters_are_explained() {
+        assert_eq!(error(""), "Empty filter");
+        assert_eq!(error("(tag:bug"), "Missing closing parenthesis in filter");
+        assert_eq!(error("tag:bug )"), "Unexpected token in filter: Close");
+        assert_eq!(error("tag:bug and"), "Unexpected end of filter");
+        assert_eq!(
+            error("bug"),
+            "Invalid filter term: bug, expected field:value"
+        );
+        assert_eq!(error("owner:me"), "Unknown filter field: owner");
+        assert_eq!(
+            error("tag.before:bug"),
+            "Modifiers can't be used with field: tag"
+        );
+        assert_eq!(error("due.soon:friday"), "Unknown filter modifier: soon");
+        assert_eq!(
+            error("content:'fix"),
+            "Unclosed quote in filter: content:'fix"
+        );
+        assert_eq!(
+            error("status:later"),
+            "Invalid status: later, use open or done"
+        );
+    }
+}
@@ -0,0 +1,284 @@
+use chrono::{Duration, Local, NaiveDateTime};
+use comfy_table::modifiers::UTF8_ROUND_CORNERS;
+use comfy_table::presets::UTF8_FULL;
+use comfy_table::Table;
+use serde_json::Value;
+
+use crate::errors::report;
+use crate::journal::Store;
+use crate::structs::{Context, Event, EventKind, Task};
+use crate::utils::{format_task, parse_date};
+
+const HISTORY_FILE: &str = "history.jsonl";
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Appends the events found between two versions of the data to the activity log.
+pub fn record_events(store: &Store, before: &str, after: &str) {
+    let parse = |json: &str| serde_json::from_str::<Vec<Context>>(json).unwrap_or_default();
+    let events = diff_events(&parse(before), &parse(after));
+    if events.is_empty() {
+        return;
+    }
+
+    let lines: Vec<String> = events
+        .iter()
+        .map(|event| serde_json::to_string(event).expect("Error when stringifying event"))
+        .collect();
+
+    if let Err(err) = store.append(HISTORY_FILE, &format!("{}\n", lines.join("\n"))) {
+        report(err);
+    }
+}
+
+/// Prints the events since the date, of the given context (name or id) or all of them.
+pub fn print_log(store: &Store, since: Option<NaiveDateTime>, context: Option<&str>) {
+    let since = since.map(|since| since.format(DATE_FORMAT).to_string());
+    let events: Vec<Event> = store
+        .read(HISTORY_FILE)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Event>(line).ok())
+        .filter(|event| since.as_ref().is_none_or(|since| event.date >= *since))
+        .filter(|event| {
+            context.is_none_or(|ctx| event.context == ctx || event.context_id.to_string() == ctx)
+        })
+        .collect();
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS);
+
+    for event in &events {
+        table.add_row(vec![
+            event.date.to_owned(),
+            event.context.to_owned(),
+            event.kind.to_string(),
+            details(event),
+        ]);
+    }
+
+    if events.is_empty() {
+        table.add_row(vec!["No activity found"]);
+    }
+
+    println!("{table}");
+}
+
+/// Prints the changes between two versions of the data, shown by `--dry-run` instead of saving them.
+pub fn print_diff(before: &[Context], after: &[Context]) {
+    let mut lines: Vec<String> = diff_events(before, after)
+        .iter()
+        .map(|event| {
+            let sign = match event.kind {
+                EventKind::Created | EventKind::ContextCreated => '+',
+                EventKind::Deleted | EventKind::ContextDeleted => '-',
+                _ => '~',
+            };
+            match details(event) {
+                details if details.is_empty() => format!("{sign} {} {}", event.context, event.kind),
+                details => format!("{sign} {} {}: {details}", event.context, event.kind),
+            }
+        })
+        .collect();
+
+    // Changes that are not part of the activity log
+    for ctx in after {
+        let Some(old) = before.iter().find(|old| old.id == ctx.id) else {
+            continue;
+        };
+
+        if old.archived != ctx.archived {
+            let action = if ctx.archived {
+                "archived"
+            } else {
+                "unarchived"
+            };
+            lines.push(format!("~ {} context {action}", ctx.name));
+        }
+
+        let meta = |ctx: &Context| serde_json::to_value(&ctx.meta).unwrap_or_default();
+        if let (Value::Object(old_meta), Value::Object(new_meta)) = (meta(old), meta(ctx)) {
+            for (key, value) in new_meta
+                .iter()
+                .filter(|(key, value)| old_meta.get(*key) != Some(value))
+            {
+                let old_value = old_meta.get(key).cloned().unwrap_or_default();
+                lines.push(format!("~ {} {key}: {old_value} -> {value}", ctx.name));
+            }
+        }
+
+        // The kept tasks are listed in their new order, their old positions must follow it
+        let kept: Vec<usize> = pair_tasks(&old.tasks, &ctx.tasks)
+            .into_iter()
+            .filter_map(|pair| match pair {
+                (Some(old_index), Some(_)) => Some(old_index),
+                _ => None,
+            })
+            .collect();
+        if kept.windows(2).any(|pair| pair[0] > pair[1]) {
+            lines.push(format!("~ {} tasks reordered", ctx.name));
+        }
+    }
+
+    if lines.is_empty() {
+        println!("Dry run, nothing would change");
+        return;
+    }
+
+    println!("Dry run, these changes are not saved:");
+    for line in lines {
+        println!("  {line}");
+    }
+}
+
+fn details(event: &Event) -> String {
+    match (&event.before, &event.after) {
+        (Some(before), Some(after)) => format!("{before} -> {after}"),
+        (Some(value), None) | (None, Some(value)) => value.to_owned(),
+        (None, None) => String::new(),
+    }
+}
+
+/// Parses `--since`: a duration back from now (`3h`, `7d`, `2w`) or a date,
+/// a weekday being its last occurrence.
+pub fn parse_since(value: &str) -> Result<NaiveDateTime, String> {
+    let now = Local::now().naive_local();
+
+    if let Some(unit) = value.chars().last().filter(|unit| "hdw".contains(*unit)) {
+        if let Ok(count) = value[..value.len() - 1].parse::<i64>() {
+            let duration = match unit {
+                'h' => Duration::hours(count),
+                'd' => Duration::days(count),
+                _ => Duration::weeks(count),
+            };
+            return Ok(now - duration);
+        }
+    }
+
+    let mut date = parse_date(value)?;
+    if date > now.date() {
+        date -= Duration::weeks(1);
+    }
+
+    Ok(date.and_hms_opt(0, 0, 0).unwrap())
+}
+
+fn diff_events(before: &[Context], after: &[Context]) -> Vec<Event> {
+    let date = Local::now().format(DATE_FORMAT).to_string();
+    let event = |kind, ctx: &Context, before: Option<String>, after: Option<String>| Event {
+        date: date.clone(),
+        kind,
+        context_id: ctx.id,
+        context: ctx.name.clone(),
+        before,
+        after,
+    };
+    let mut events = vec![];
+
+    for ctx in before {
+        if !after.iter().any(|other| other.id == ctx.id) {
+            events.push(event(EventKind::ContextDeleted, ctx, None, None));
+        }
+    }
+
+    for ctx in after {
+        let old = before.iter().find(|other| other.id == ctx.id);
+        if old.is_none() {
+            events.push(event(EventKind::ContextCreated, ctx, None, None));
+        }
+
+        if ctx.active && !old.is_some_and(|old| old.active) {
+            let previous = before
+                .iter()
+                .find(|ctx| ctx.active)
+                .map(|ctx| ctx.name.clone());
+            let current = Some(ctx.name.clone());
+            events.push(event(EventKind::ContextSwitched, ctx, previous, current));
+        }
+
+        let Some(old) = old else {
+            continue;
+        };
+
+        if old.name != ctx.name {
+            let names = (Some(old.name.clone()), Some(ctx.name.clone()));
+            events.push(event(EventKind::ContextRenamed, ctx, names.0, names.1));
+        }
+
+        let pairs = pair_tasks(&old.tasks, &ctx.tasks);
+
+        for pair in &pairs {
+            if let (Some(old_index), None) = pair {
+                let task = format_task(&old.tasks[*old_index]);
+                events.push(event(EventKind::Deleted, ctx, Some(task), None));
+            }
+        }
+
+        for (old_index, index) in &pairs {
+            let Some(task) = index.map(|index| &ctx.tasks[index]) else {
+                continue;
+            };
+            let Some(old_task) = old_index.map(|index| &old.tasks[index]) else {
+                events.push(event(
+                    EventKind::Created,
+                    ctx,
+                    None,
+                    Some(format_task(task)),
+                ));
+                continue;
+            };
+
+            if format_task(old_task) != format_task(task) {
+                let values = (Some(format_task(old_task)), Some(format_task(task)));
+                events.push(event(EventKind::Edited, ctx, values.0, values.1));
+            }
+
+            if old_task.done != task.done {
+                let kind = if task.done {
+                    EventKind::Completed
+                } else {
+                    EventKind::Reopened
+                };
+                events.push(event(kind, ctx, None, Some(format_task(task))));
+            }
+        }
+    }
+
+    events
+}
+
+/// Pairs the positions of the versions of each task in two lists of a context, in the order
+/// of `after`, then the removed tasks. Tasks are matched by creation date as their ids change
+/// when others are removed or moved, the ones sharing a date (like a batch add) by content
+/// then by order.
+pub fn pair_tasks(before: &[Task], after: &[Task]) -> Vec<(Option<usize>, Option<usize>)> {
+    let mut unmatched: Vec<bool> = vec![true; before.len()];
+    let mut matched: Vec<Option<usize>> = vec![None; after.len()];
+
+    for same_content in [true, false] {
+        for (index, task) in after.iter().enumerate() {
+            if matched[index].is_some() {
+                continue;
+            }
+
+            matched[index] = before.iter().enumerate().position(|(old_index, old)| {
+                unmatched[old_index]
+                    && old.creation_date == task.creation_date
+                    && (!same_content || old.content == task.content)
+            });
+            if let Some(old_index) = matched[index] {
+                unmatched[old_index] = false;
+            }
+        }
+    }
+
+    let removed = (0..before.len()).filter(|index| unmatched[*index]);
+
+    matched
+        .into_iter()
+        .enumerate()
+        .map(|(index, old_index)| (old_index, Some(index)))
+        .chain(removed.map(|old_index| (Some(old_index), None)))
+        .collect()
+}
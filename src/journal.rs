@@ -0,0 +1,305 @@
+use chrono::Local;
+use ssh2::{OpenFlags, OpenType, Sftp};
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Write};
+use std::path::Path;
+
+use crate::errors::{report, report_as, Error, TaskError};
+use crate::history::{pair_tasks, record_events};
+use crate::output::record_changes;
+use crate::structs::{Config, Context, Journal, JournalEntry, Task};
+use crate::utils::{get_remote_file, print_message};
+
+const DATA_FILE: &str = "tasks.json";
+const JOURNAL_FILE: &str = "journal.json";
+/// Operations kept in each of the undo and redo stacks
+const MAX_ENTRIES: usize = 50;
+
+/// Where the data file and its journal are stored.
+pub enum Store<'a> {
+    Local(&'a Config),
+    Remote(&'a Config, &'a Sftp),
+}
+
+impl Store<'_> {
+    fn config(&self) -> &Config {
+        match self {
+            Store::Local(config) | Store::Remote(config, _) => config,
+        }
+    }
+
+    pub fn read(&self, name: &str) -> Option<String> {
+        match self {
+            Store::Local(config) => fs::read_to_string(local_file(config, name)).ok(),
+            Store::Remote(config, sftp) => {
+                let mut text = String::new();
+                let path = get_remote_file(config, name);
+                let mut file = sftp.open(Path::new(&path)).ok()?;
+                file.read_to_string(&mut text).ok()?;
+                Some(text)
+            }
+        }
+    }
+
+    fn write(&self, name: &str, text: &str) -> Result<(), Error> {
+        let error = |err: &dyn std::error::Error| {
+            Error::storage(format!("Error when writing {name}: {err}"))
+        };
+
+        match self {
+            Store::Local(config) => {
+                fs::write(local_file(config, name), text).map_err(|err| error(&err))
+            }
+            Store::Remote(config, sftp) => {
+                let path = get_remote_file(config, name);
+                let mut file = sftp.create(Path::new(&path)).map_err(|err| error(&err))?;
+                file.write_all(text.as_bytes()).map_err(|err| error(&err))?;
+                file.close().map_err(|err| error(&err))
+            }
+        }
+    }
+
+    /// Adds the text at the end of the file, creating it when missing.
+    pub fn append(&self, name: &str, text: &str) -> Result<(), Error> {
+        let error = |err: &dyn std::error::Error| {
+            Error::storage(format!("Error when writing {name}: {err}"))
+        };
+
+        match self {
+            Store::Local(config) => OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(local_file(config, name))
+                .and_then(|mut file| file.write_all(text.as_bytes()))
+                .map_err(|err| error(&err)),
+            Store::Remote(config, sftp) => {
+                let path = get_remote_file(config, name);
+                let flags = OpenFlags::WRITE | OpenFlags::APPEND | OpenFlags::CREATE;
+                let mut file = sftp
+                    .open_mode(Path::new(&path), flags, 0o644, OpenType::File)
+                    .map_err(|err| error(&err))?;
+                file.write_all(text.as_bytes()).map_err(|err| error(&err))?;
+                file.close().map_err(|err| error(&err))
+            }
+        }
+    }
+
+    fn journal(&self) -> Journal {
+        self.read(JOURNAL_FILE)
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    fn save_journal(&self, journal: &Journal) -> Result<(), Error> {
+        let json = serde_json::to_string(journal).expect("Error when stringifying journal");
+        self.write(JOURNAL_FILE, &json)
+    }
+}
+
+/// Writes the data file, `before` being the data the command loaded, and records the
+/// operation so it can be undone. The journal is saved first, the data is left as it was
+/// when that fails.
+pub fn write_data(store: &Store, before: &str, json: &str) -> Result<(), Error> {
+    if before == json {
+        return Ok(());
+    }
+
+    let previous = store.journal();
+    let mut journal = previous.clone();
+    // The data was changed outside of task, the operations before can't be undone anymore
+    if before != journal.head {
+        journal.undo.clear();
+    }
+    journal.undo.push(JournalEntry {
+        command: store.config().command.clone(),
+        date: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        data: before.to_owned(),
+    });
+    journal.head = json.to_owned();
+    journal.redo.clear();
+    trim(&mut journal.undo);
+
+    store.save_journal(&journal)?;
+
+    if let Err(err) = store.write(DATA_FILE, json) {
+        // Keeps the journal matching the data which wasn't written
+        if let Err(err) = store.save_journal(&previous) {
+            report(err);
+        }
+        return Err(err);
+    }
+
+    record_events(store, before, json);
+    Ok(())
+}
+
+fn parse(json: &str) -> Vec<Context> {
+    serde_json::from_str(json).unwrap_or_default()
+}
+
+/// Reverts the last operations, stopping when the data changed since one of them.
+pub fn undo(store: &Store, steps: usize) {
+    replay(store, steps, true);
+}
+
+/// Applies again the last undone operations.
+pub fn redo(store: &Store, steps: usize) {
+    replay(store, steps, false);
+}
+
+/// With --dry-run, only shows what would be reverted or applied.
+fn replay(store: &Store, steps: usize, undo: bool) {
+    let mut journal = store.journal();
+    let dry_run = store.config().dry_run;
+    let action = match (undo, dry_run) {
+        (true, false) => "Undone",
+        (false, false) => "Redone",
+        (true, true) => "Would undo",
+        (false, true) => "Would redo",
+    };
+    let mut current = store.read(DATA_FILE).unwrap_or(String::from("[]"));
+
+    for _ in 0..steps.max(1) {
+        let stack = if undo {
+            &mut journal.undo
+        } else {
+            &mut journal.redo
+        };
+        let Some(mut entry) = stack.pop() else {
+            let message = format!("Nothing to {}", if undo { "undo" } else { "redo" });
+            print_message(store.config(), message);
+            break;
+        };
+
+        let expected = journal.head.clone();
+        if current != expected {
+            report_as(
+                TaskError::Conflict,
+                format!(
+                    "The tasks were changed outside of task after {}, it can't be {}",
+                    entry.command,
+                    if undo { "undone" } else { "redone" }
+                ),
+            );
+            stack.push(entry);
+            break;
+        }
+
+        let target = entry.data.clone();
+        if !dry_run {
+            if let Err(err) = store.write(DATA_FILE, &target) {
+                report(err);
+                stack.push(entry);
+                break;
+            }
+            record_events(store, &expected, &target);
+            record_changes(store.config(), &parse(&expected), &parse(&target));
+        }
+
+        // What a dry run would do is shown even with --quiet
+        if !store.config().quiet || dry_run {
+            println!("{action}: {} ({})", entry.command, entry.date);
+            for line in describe(&expected, &target) {
+                println!("  {line}");
+            }
+        }
+
+        // The entry moves to the other stack with the data on its other side
+        entry.data = expected;
+        journal.head = target.clone();
+        current = target;
+
+        if undo {
+            journal.redo.push(entry);
+        } else {
+            journal.undo.push(entry);
+        }
+    }
+
+    if !dry_run {
+        if let Err(err) = store.save_journal(&journal) {
+            report(err);
+        }
+    }
+}
+
+/// What changed between two versions of the data, context by context.
+fn describe(from: &str, to: &str) -> Vec<String> {
+    let (from, to) = (parse(from), parse(to));
+    let mut lines = vec![];
+
+    for ctx in &from {
+        if !to.iter().any(|other| other.id == ctx.id) {
+            lines.push(format!("context {} removed", ctx.name));
+        }
+    }
+
+    for ctx in &to {
+        let Some(old) = from.iter().find(|other| other.id == ctx.id) else {
+            lines.push(format!("context {} added", ctx.name));
+            continue;
+        };
+
+        let mut changes = vec![];
+        if old.name != ctx.name {
+            changes.push(format!("renamed from {}", old.name));
+        }
+        if old.active != ctx.active {
+            changes.push(String::from(if ctx.active { "active" } else { "inactive" }));
+        }
+
+        let pairs = pair_tasks(&old.tasks, &ctx.tasks);
+        let added = pairs.iter().filter(|(old, _)| old.is_none()).count();
+        let removed = pairs.iter().filter(|(_, new)| new.is_none()).count();
+        let changed = pairs
+            .iter()
+            .filter(|pair| match pair {
+                (Some(a), Some(b)) => !same_fields(&old.tasks[*a], &ctx.tasks[*b]),
+                _ => false,
+            })
+            .count();
+
+        for (count, label) in [(added, "added"), (removed, "removed"), (changed, "changed")] {
+            match count {
+                0 => {}
+                1 => changes.push(format!("1 task {label}")),
+                count => changes.push(format!("{count} tasks {label}")),
+            }
+        }
+
+        if changes.is_empty() && to_json(old) != to_json(ctx) {
+            changes.push(String::from("updated"));
+        }
+        if !changes.is_empty() {
+            lines.push(format!("{}: {}", ctx.name, changes.join(", ")));
+        }
+    }
+
+    lines
+}
+
+/// Compares the tasks without their position.
+fn same_fields(a: &Task, b: &Task) -> bool {
+    let (mut a, mut b) = (a.clone(), b.clone());
+    a.id = 0;
+    b.id = 0;
+    to_json(&a) == to_json(&b)
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+fn trim(entries: &mut Vec<JournalEntry>) {
+    if entries.len() > MAX_ENTRIES {
+        entries.drain(..entries.len() - MAX_ENTRIES);
+    }
+}
+
+fn local_file(config: &Config, name: &str) -> String {
+    if name == DATA_FILE {
+        config.local_file_path.clone()
+    } else {
+        format!("{}/{name}", config.folder_path)
+    }
+}
@@ -1,63 +1,437 @@
-use clap::{Command, CommandFactory};
-use clap_complete::{generate, Generator};
+use clap::builder::Resettable;
+use clap::{Command, CommandFactory, FromArgMatches, Parser};
+use clap_complete::{generate, Shell};
 
-use std::io;
+use std::path::PathBuf;
+use std::{env, fs, io, process};
 
+mod aliases;
 mod args;
+mod completion;
+mod confirm;
+mod edit;
+mod errors;
+mod filter;
+mod history;
+mod journal;
+mod modify;
+mod output;
+mod picker;
+mod plugins;
 mod services;
 mod structs;
+mod tui;
 mod utils;
+mod watch;
 
-use services::api::migrate;
+use aliases::{aliases_help, expand_aliases, valid_aliases};
+use completion::{clear_cache, dynamic_script, print_candidates};
+use confirm::{clear_summary, confirm, rmc_summary};
+use errors::{exit_code, report, report_as, Error, TaskError};
+use filter::Filter;
+use history::parse_since;
+use modify::{parse_changes, parse_new_tasks, split_where};
+use output::{print_changes, show_task};
+use picker::{is_interactive, pick_context, pick_tasks, prompt};
+use plugins::{find_plugin, plugin_stdin, run_plugin};
 use services::api::ApiService;
+use services::api::{migrate, migrate_summary};
 use services::file::FileService;
-use structs::{Config, Service, UserConfig};
+use structs::{Config, ListOptions, Service, UserConfig};
 
-use args::{Cli, Commands};
-use clap::Parser;
+use args::{Cli, Commands, ListArgs};
+use tui::run_tui;
+use utils::{print_message, print_reports};
+use watch::watch;
 
 fn main() {
-    let cli = Cli::parse();
     let user_config: UserConfig =
         confy::load("tasks", "config").expect("Error when loading the config file");
 
     let mut cli_cmd = Cli::command();
+    let aliases = valid_aliases(&user_config.aliases, &cli_cmd);
+    if !aliases.is_empty() {
+        cli_cmd = cli_cmd.after_help(aliases_help(&aliases));
+    }
+
+    let args = match expand_aliases(env::args().collect(), &aliases, &cli_cmd) {
+        Ok(args) => args,
+        Err(err) => {
+            report_as(TaskError::InvalidInput, err);
+            process::exit(exit_code());
+        }
+    };
+
+    if !is_interactive() {
+        cli_cmd = required_ids(cli_cmd);
+    }
+
+    let matches = cli_cmd.clone().get_matches_from(&args);
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+
     if let Some(generator) = cli.generator {
         print_completions(generator, &mut cli_cmd);
         return;
     }
 
-    let config = Config::new(user_config);
+    let config = Config::new(user_config, &cli, command_line(&args[1..]));
+    let completing = matches!(cli.command, Some(Commands::Complete(_)));
 
     if config.api_url.is_empty() {
-        run_cmd(&config, cli, &FileService);
+        run_with_format(&config, cli, &FileService);
     } else {
-        run_cmd(&config, cli, &ApiService);
+        run_with_format(&config, cli, &ApiService);
+    }
+
+    // Any other command may change the data, the API one included, the candidates are
+    // fetched again on the next completion
+    if !completing {
+        clear_cache();
+    }
+
+    process::exit(exit_code());
+}
+
+/// With --format json or jsonl, the commands changing the data print the changed items instead,
+/// and with --quiet they print nothing but the errors.
+fn run_with_format(config: &Config, cli: Cli, data_service: &impl Service) {
+    let mutating = matches!(
+        cli.command,
+        Some(
+            Commands::Use(_)
+                | Commands::Up(_)
+                | Commands::Upc(_)
+                | Commands::Add(_)
+                | Commands::Rm(_)
+                | Commands::Rmc(_)
+                | Commands::Done(_)
+                | Commands::Setc(_)
+                | Commands::Archivec(_)
+                | Commands::Unarchivec(_)
+                | Commands::Modify(_)
+                | Commands::Mvup(_)
+                | Commands::Mvdown(_)
+                | Commands::Order(_)
+                | Commands::Undo(_)
+                | Commands::Redo(_)
+                | Commands::Clear
+                | Commands::Edit(_)
+        )
+    );
+
+    if mutating && config.format.is_json() {
+        print_changes(config, || run_cmd(config, cli, data_service));
+    } else {
+        run_cmd(config, cli, data_service);
     }
 }
 
 fn run_cmd(config: &Config, cli: Cli, data_service: &impl Service) {
-    if let None = cli.command {
-        data_service.list_tasks(&config, false);
+    let Some(command) = cli.command else {
+        match list_options(config, ListArgs::default(), false) {
+            Ok(options) => data_service.list_tasks(config, options),
+            Err(err) => report_as(TaskError::InvalidInput, err),
+        }
         return;
+    };
+
+    match command {
+        Commands::Use(cmd) => {
+            if let Some(name) = cmd.name.or_else(|| pick_context(config, data_service)) {
+                data_service.use_context(config, name)
+            }
+        }
+        Commands::Up(cmd) => {
+            let Some(id) = cmd.id.or_else(|| pick_tasks(config, data_service, false)) else {
+                return;
+            };
+            if let Some(name) = cmd.name.or_else(|| prompt("New content: ")) {
+                data_service.edit_task(config, id, name)
+            }
+        }
+        Commands::Upc(cmd) => data_service.edit_context(config, cmd.name, cmd.new_name),
+        Commands::Add(cmd) => {
+            let input = match (cmd.name, cmd.file) {
+                (Some(name), None) if name != "-" => {
+                    data_service.add_task(config, name);
+                    return;
+                }
+                (_, Some(file)) => fs::read_to_string(&file)
+                    .map_err(|err| Error::storage(format!("Error when reading {file}: {err}"))),
+                _ => io::read_to_string(io::stdin())
+                    .map_err(|err| Error::storage(format!("Error when reading stdin: {err}"))),
+            };
+
+            match input.and_then(|input| parse_new_tasks(&input).map_err(Error::invalid)) {
+                Ok(tasks) if tasks.is_empty() => print_message(config, "No tasks to add"),
+                Ok(tasks) => data_service.add_tasks(config, tasks),
+                Err(err) => report(err),
+            }
+        }
+        Commands::Rm(cmd) => {
+            let Some(ids) = select_arg(config, cmd.name, &cmd.filter, data_service) else {
+                return;
+            };
+            match parse_filter(cmd.filter.unwrap_or_default()) {
+                Ok(filter) => data_service.del_task(config, ids, filter),
+                Err(err) => report_as(TaskError::InvalidInput, err),
+            }
+        }
+        Commands::Rmc(cmd) => match rmc_summary(config, data_service, &cmd.name) {
+            Ok(summary) if confirm(config, "rmc", &summary) => {
+                data_service.del_context(config, cmd.name)
+            }
+            Ok(_) => {}
+            Err(err) => report(err),
+        },
+        Commands::Ls(cmd) => list_tasks(config, cmd, false, data_service),
+        Commands::Lsa(cmd) => list_tasks(config, cmd, true, data_service),
+        Commands::Search(cmd) => data_service.search_tasks(config, cmd.text, cmd.regex),
+        Commands::Show(cmd) => show_task(config, data_service, cmd.id),
+        Commands::Lsc(cmd) => data_service.list_contexts(config, cmd.all),
+        Commands::Done(cmd) => {
+            let Some(ids) = select_arg(config, cmd.name, &cmd.filter, data_service) else {
+                return;
+            };
+            match parse_filter(cmd.filter.unwrap_or_default()) {
+                Ok(filter) => data_service.mark_done(config, ids, filter),
+                Err(err) => report_as(TaskError::InvalidInput, err),
+            }
+        }
+        Commands::Setc(cmd) => data_service.set_context_meta(config, cmd.name, cmd.key, cmd.value),
+        Commands::Archivec(cmd) => data_service.archive_context(config, cmd.name, true),
+        Commands::Unarchivec(cmd) => data_service.archive_context(config, cmd.name, false),
+        Commands::Modify(cmd) => match split_where(cmd.changes, cmd.filter) {
+            Ok((changes, filter)) => {
+                let changes = parse_changes(&changes);
+                match (changes, parse_filter(filter.unwrap_or_default())) {
+                    (Ok(changes), Ok(filter)) => {
+                        data_service.modify_tasks(config, cmd.name, changes, filter)
+                    }
+                    (Err(err), _) | (_, Err(err)) => report_as(TaskError::InvalidInput, err),
+                }
+            }
+            Err(err) => report_as(TaskError::InvalidInput, err),
+        },
+        Commands::Mvup(cmd) => data_service.move_task(config, cmd.id, cmd.id.saturating_sub(1)),
+        Commands::Mvdown(cmd) => data_service.move_task(config, cmd.id, cmd.id + 1),
+        Commands::Order(cmd) => data_service.move_task(config, cmd.id, cmd.position),
+        Commands::Report(cmd) => match report_options(config, &cmd.name, cmd.filter) {
+            Ok(options) => data_service.list_tasks(config, options),
+            Err(err) => report(err),
+        },
+        Commands::Reports => print_reports(config),
+        Commands::Tui => run_tui(config, data_service),
+        Commands::Edit(cmd) => data_service.edit_tasks(config, cmd.context),
+        Commands::External(args) => {
+            let (name, rest) = args.split_first().expect("No subcommand");
+            if config.reports.contains_key(name) {
+                match report_options(config, name, rest.to_vec()) {
+                    Ok(options) => data_service.list_tasks(config, options),
+                    Err(err) => report(err),
+                }
+                return;
+            }
+
+            match find_plugin(name) {
+                Some(path) => run_external(config, &path, rest, data_service),
+                None => unknown_command(name),
+            }
+        }
+        Commands::Log(cmd) => match cmd.since.as_deref().map(parse_since).transpose() {
+            Ok(since) => data_service.show_log(config, since),
+            Err(err) => report_as(TaskError::InvalidInput, err),
+        },
+        Commands::Undo(cmd) => data_service.undo(config, cmd.steps),
+        Commands::Redo(cmd) => data_service.redo(config, cmd.steps),
+        Commands::Clear => match clear_summary(config, data_service) {
+            Ok(summary) if confirm(config, "clear", &summary) => data_service.clear_tasks(config),
+            Ok(_) => {}
+            Err(err) => report(err),
+        },
+        Commands::Migrate => match migrate_summary(config) {
+            Ok(summary) if confirm(config, "migrate", &summary) => migrate(config),
+            Ok(_) => {}
+            Err(err) => report(err),
+        },
+        Commands::Complete(cmd) => print_candidates(config, data_service, cmd.kind),
+    }
+}
+
+/// Exits with the error clap gives for an unknown subcommand, suggesting the closest ones.
+fn unknown_command(name: &str) {
+    // The value parser set by the derive would allow them again
+    let cmd = Cli::command()
+        .allow_external_subcommands(false)
+        .external_subcommand_value_parser(Resettable::Reset);
+    match cmd.try_get_matches_from(["task", name]) {
+        Err(err) => err.exit(),
+        Ok(_) => report_as(
+            TaskError::InvalidInput,
+            format!("Unknown command: {}, see task --help", name),
+        ),
+    }
+}
+
+/// Runs a `task-{name}` plugin then applies the commands it returned.
+fn run_external(config: &Config, path: &PathBuf, args: &[String], data_service: &impl Service) {
+    let mutations = match plugin_stdin(config, path) {
+        true => data_service.get_contexts(config).map(Some),
+        false => Ok(None),
+    }
+    .and_then(|contexts| run_plugin(config, path, args, contexts));
+
+    let mutations = match mutations {
+        Ok(mutations) => mutations,
+        Err(err) => {
+            report(err);
+            return;
+        }
+    };
+
+    for mutation in mutations {
+        let args = std::iter::once(String::from("task")).chain(mutation.iter().cloned());
+        match Cli::try_parse_from(args) {
+            Ok(Cli {
+                command: Some(Commands::External(_)),
+                ..
+            }) => report_as(
+                TaskError::InvalidInput,
+                format!("Plugins can't run other plugins: {}", mutation.join(" ")),
+            ),
+            Ok(cli) if cli.command.is_some() => {
+                let config = config.with_options(&cli, command_line(&mutation));
+                run_cmd(&config, cli, data_service)
+            }
+            _ => report_as(
+                TaskError::InvalidInput,
+                format!("Invalid command from plugin: {}", mutation.join(" ")),
+            ),
+        }
     }
+}
+
+/// The command as it is run once the aliases are expanded, quoting the arguments with spaces.
+fn command_line(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| match arg.contains(char::is_whitespace) {
+            true => format!("\"{arg}\""),
+            false => arg.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
 
-    match &cli.command.unwrap() {
-        Commands::Use(cmd) => data_service.use_context(&config, cmd.name.clone()),
-        Commands::Up(cmd) => data_service.edit_task(&config, cmd.id.clone(), cmd.name.clone()),
-        Commands::Upc(cmd) => data_service.edit_context(&config, cmd.id.clone(), cmd.name.clone()),
-        Commands::Add(cmd) => data_service.add_task(&config, cmd.name.clone()),
-        Commands::Rm(cmd) => data_service.del_task(&config, cmd.name.clone()),
-        Commands::Rmc(cmd) => data_service.del_context(&config, cmd.name.clone()),
-        Commands::Ls => data_service.list_tasks(&config, false),
-        Commands::Lsa => data_service.list_tasks(&config, true),
-        Commands::Lsc => data_service.list_contexts(&config),
-        Commands::Done(cmd) => data_service.mark_done(&config, cmd.name.clone()),
-        Commands::Clear => data_service.clear_tasks(&config),
-        Commands::Migrate => migrate(&config),
+/// The ids given on the command line, none selecting every task matching the filter,
+/// or the tasks picked when both are omitted.
+fn select_arg(
+    config: &Config,
+    name: Option<String>,
+    filter: &Option<String>,
+    data_service: &impl Service,
+) -> Option<String> {
+    match (name, filter) {
+        (Some(name), _) => Some(name),
+        (None, Some(_)) => Some(String::new()),
+        (None, None) => pick_tasks(config, data_service, true),
     }
 }
 
-fn print_completions<G: Generator>(gen: G, cmd: &mut Command) {
-    generate(gen, cmd, cmd.get_name().to_string(), &mut io::stdout());
+/// The ids of done, rm, up and use are only optional in a terminal, where the picker is opened.
+fn required_ids(cmd: Command) -> Command {
+    cmd.mut_subcommand("done", |sub| {
+        sub.mut_arg("name", |arg| arg.required_unless_present("filter"))
+    })
+    .mut_subcommand("rm", |sub| {
+        sub.mut_arg("name", |arg| arg.required_unless_present("filter"))
+    })
+    .mut_subcommand("up", |sub| {
+        sub.mut_arg("id", |arg| arg.required(true))
+            .mut_arg("name", |arg| arg.required(true))
+    })
+    .mut_subcommand("use", |sub| sub.mut_arg("name", |arg| arg.required(true)))
+}
+
+fn parse_filter(filter: String) -> Result<Option<Filter>, String> {
+    if filter.trim().is_empty() {
+        return Ok(None);
+    }
+
+    Filter::parse(&filter).map(Some)
+}
+
+/// Lists the tasks once, or keeps redrawing them with --watch.
+fn list_tasks(config: &Config, args: ListArgs, all: bool, data_service: &impl Service) {
+    let watching = args.watch;
+
+    match list_options(config, args, all) {
+        Ok(options) if watching => watch(config, data_service, options),
+        Ok(options) => data_service.list_tasks(config, options),
+        Err(err) => report_as(TaskError::InvalidInput, err),
+    }
+}
+
+/// Builds the listing options, sort and group falling back to the config ones.
+fn list_options(config: &Config, args: ListArgs, all: bool) -> Result<ListOptions, String> {
+    let sort = args.sort.unwrap_or(config.sort.clone());
+    let group_by = args.group_by.unwrap_or(config.group_by.clone());
+
+    Ok(ListOptions {
+        all,
+        filter: parse_filter(args.filter.join(" "))?,
+        sort: if sort.is_empty() {
+            None
+        } else {
+            Some(sort.parse()?)
+        },
+        group_by: if group_by.is_empty() {
+            None
+        } else {
+            Some(group_by.parse()?)
+        },
+        columns: vec![],
+        contexts: vec![],
+    })
+}
+
+/// Builds the listing options of a report, the extra filter being added to the report one.
+fn report_options(config: &Config, name: &str, filter: Vec<String>) -> Result<ListOptions, Error> {
+    let report = config.reports.get(name).ok_or(Error::not_found(format!(
+        "No report found with this name: {name}"
+    )))?;
+
+    let filter = match (report.filter.trim(), filter.join(" ")) {
+        (report_filter, extra) if extra.trim().is_empty() => report_filter.to_owned(),
+        ("", extra) => extra,
+        (report_filter, extra) => format!("({report_filter}) and ({extra})"),
+    };
+    let optional = |value: &String| (!value.is_empty()).then(|| value.to_owned());
+
+    let args = ListArgs {
+        filter: vec![filter],
+        sort: optional(&report.sort),
+        group_by: optional(&report.group_by),
+        watch: false,
+    };
+
+    let mut options = list_options(config, args, report.all).map_err(Error::invalid)?;
+    options.columns = report
+        .columns
+        .iter()
+        .map(|column| column.parse())
+        .collect::<Result<_, _>>()
+        .map_err(Error::invalid)?;
+    options.contexts = report.contexts.clone();
+
+    Ok(options)
+}
+
+/// Prints the static completions then, for bash, zsh and fish, the script
+/// completing context names and task ids through `task complete`.
+fn print_completions(shell: Shell, cmd: &mut Command) {
+    generate(shell, cmd, cmd.get_name().to_string(), &mut io::stdout());
+
+    if let Some(script) = dynamic_script(shell) {
+        print!("{script}");
+    }
 }
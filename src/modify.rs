@@ -0,0 +1,438 @@
+use chrono::Local;
+
+use crate::structs::{Config, Context, Priority, Task};
+use crate::utils::{parse_date, print_message};
+
+/// A change applied by `modify` to each selected task.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Content(String),
+    Replace(String, String),
+    Done(bool),
+    AddTag(String),
+    RemoveTag(String),
+    Tags(Vec<String>),
+    Priority(Option<Priority>),
+    Due(Option<String>),
+}
+
+impl Change {
+    /// Parses `content:text`, `/old/new/`, `status:done|open`, `+tag`, `-tag`,
+    /// `tags:a,b`, `priority:H` or `due:friday`, an empty value resets the field.
+    pub fn parse(input: &str) -> Result<Self, String> {
+        if let Some(tag) = input.strip_prefix('+') {
+            return Ok(Change::AddTag(parse_tag(tag)?));
+        }
+
+        // Options given after the changes are taken as changes, e.g. --dry-run
+        if input.starts_with("--") {
+            return Err(format!(
+                "Unknown change: {input}, put the options before the ids"
+            ));
+        }
+
+        if let Some(tag) = input.strip_prefix('-') {
+            return Ok(Change::RemoveTag(parse_tag(tag)?));
+        }
+
+        if let Some(replace) = input.strip_prefix('/') {
+            let replace = replace.strip_suffix('/').unwrap_or(replace);
+            return match replace.split_once('/') {
+                Some((from, to)) if !from.is_empty() => {
+                    Ok(Change::Replace(from.to_owned(), to.to_owned()))
+                }
+                _ => Err(format!("Invalid replacement: {input}, use /old/new/")),
+            };
+        }
+
+        let Some((key, value)) = input.split_once(':') else {
+            return Err(format!("Unknown change: {input}"));
+        };
+
+        match key.to_lowercase().as_str() {
+            "content" if value.is_empty() => Err(String::from("The content can't be empty")),
+            "content" => Ok(Change::Content(value.to_owned())),
+            "status" => match value.to_lowercase().as_str() {
+                "done" | "completed" => Ok(Change::Done(true)),
+                "open" | "pending" | "todo" => Ok(Change::Done(false)),
+                _ => Err(format!("Invalid status: {value}, use open or done")),
+            },
+            "tags" | "tag" => Ok(Change::Tags(
+                value
+                    .split(',')
+                    .filter(|tag| !tag.trim().is_empty())
+                    .map(parse_tag)
+                    .collect::<Result<_, _>>()?,
+            )),
+            "priority" | "pri" if value.is_empty() => Ok(Change::Priority(None)),
+            "priority" | "pri" => Ok(Change::Priority(Some(value.parse()?))),
+            "due" if value.is_empty() => Ok(Change::Due(None)),
+            "due" => Ok(Change::Due(Some(
+                parse_date(value)?.format("%Y-%m-%d").to_string(),
+            ))),
+            _ => Err(format!("Unknown change: {input}")),
+        }
+    }
+
+    /// Applies the change, returning its description when the task was modified.
+    fn apply(&self, task: &mut Task) -> Option<String> {
+        match self {
+            Change::Content(content) if task.content != *content => {
+                let change = format!("content \"{}\" -> \"{content}\"", task.content);
+                task.content = content.to_owned();
+                Some(change)
+            }
+            Change::Replace(from, to) if task.content.contains(from.as_str()) => {
+                let content = task.content.replace(from.as_str(), to);
+                let change = format!("content \"{}\" -> \"{content}\"", task.content);
+                task.content = content;
+                Some(change)
+            }
+            Change::Done(done) if task.done != *done => {
+                task.done = *done;
+                Some(String::from(if *done { "done" } else { "open" }))
+            }
+            Change::AddTag(tag) if !task.tags.contains(tag) => {
+                task.tags.push(tag.to_owned());
+                Some(format!("+{tag}"))
+            }
+            Change::RemoveTag(tag) if task.tags.contains(tag) => {
+                task.tags.retain(|t| t != tag);
+                Some(format!("-{tag}"))
+            }
+            Change::Tags(tags) if task.tags != *tags => {
+                let change = format!("tags [{}] -> [{}]", task.tags.join(","), tags.join(","));
+                task.tags = tags.to_owned();
+                Some(change)
+            }
+            Change::Priority(priority) if task.priority != *priority => {
+                let change = format!(
+                    "priority {} -> {}",
+                    display_option(&task.priority),
+                    display_option(priority)
+                );
+                task.priority = *priority;
+                Some(change)
+            }
+            Change::Due(due) if task.due != *due => {
+                let change = format!(
+                    "due {} -> {}",
+                    display_option(&task.due),
+                    display_option(due)
+                );
+                task.due = due.to_owned();
+                Some(change)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Task read by a batch add, one per line like `Deploy the API +ops priority:H due:friday`.
+#[derive(Debug, Clone)]
+pub struct NewTask {
+    pub content: String,
+    pub changes: Vec<Change>,
+}
+
+impl NewTask {
+    /// Splits the `+tag`, `tags:`, `priority:` and `due:` words from the content.
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut content = vec![];
+        let mut changes = vec![];
+
+        for word in line.split_whitespace() {
+            let key = word.split_once(':').map(|(key, _)| key.to_lowercase());
+            let inline = match key.as_deref() {
+                Some("tags" | "tag" | "priority" | "pri" | "due") => true,
+                _ => word.len() > 1 && word.starts_with('+'),
+            };
+
+            if inline {
+                changes.push(Change::parse(word)?);
+            } else {
+                content.push(word);
+            }
+        }
+
+        if content.is_empty() {
+            return Err(format!("The content can't be empty: {line}"));
+        }
+
+        Ok(NewTask {
+            content: content.join(" "),
+            changes,
+        })
+    }
+
+    /// Builds the task with the context defaults, then applies the inline changes.
+    pub fn to_task(&self, ctx: &Context, id: usize) -> Task {
+        let date = Local::now().to_string();
+        let mut task = Task {
+            id,
+            content: self.content.clone(),
+            done: false,
+            creation_date: date.clone(),
+            modification_date: date,
+            priority: ctx.meta.default_priority,
+            tags: ctx.meta.default_tags.clone(),
+            due: None,
+        };
+
+        for change in &self.changes {
+            change.apply(&mut task);
+        }
+
+        task
+    }
+}
+
+/// Parses the lines of a batch add, skipping the empty ones and `#` comments.
+pub fn parse_new_tasks(input: &str) -> Result<Vec<NewTask>, String> {
+    input
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(NewTask::parse)
+        .collect()
+}
+
+/// Takes a `--where` written after the changes out of them, clap reading every word
+/// after the ids as a change so that removed tags like `-urgent` are accepted.
+pub fn split_where(
+    changes: Vec<String>,
+    filter: Option<String>,
+) -> Result<(Vec<String>, Option<String>), String> {
+    let mut filter = filter;
+    let mut kept = Vec::new();
+    let mut words = changes.into_iter();
+
+    while let Some(word) = words.next() {
+        let value = match word.as_str() {
+            "--where" | "-w" => words
+                .next()
+                .ok_or_else(|| String::from("Missing filter after --where"))?,
+            _ => match word.strip_prefix("--where=") {
+                Some(value) => value.to_owned(),
+                None => {
+                    kept.push(word);
+                    continue;
+                }
+            },
+        };
+
+        if filter.replace(value).is_some() {
+            return Err(String::from("Only one --where filter can be given"));
+        }
+    }
+
+    if kept.is_empty() {
+        return Err(String::from("No changes given"));
+    }
+
+    Ok((kept, filter))
+}
+
+pub fn parse_changes(changes: &[String]) -> Result<Vec<Change>, String> {
+    changes.iter().map(|change| Change::parse(change)).collect()
+}
+
+/// Applies the changes to the selected tasks and returns a summary line for each modified one.
+pub fn apply_changes(tasks: &mut [Task], ids: &[usize], changes: &[Change]) -> Vec<String> {
+    let date = Local::now().to_string();
+
+    tasks
+        .iter_mut()
+        .filter(|task| ids.contains(&task.id))
+        .filter_map(|task| {
+            let applied: Vec<String> = changes.iter().filter_map(|c| c.apply(task)).collect();
+            if applied.is_empty() {
+                return None;
+            }
+
+            task.modification_date = date.clone();
+            Some(format!("Task {}: {}", task.id, applied.join(", ")))
+        })
+        .collect()
+}
+
+pub fn print_summary(config: &Config, summary: &[String]) {
+    let mut lines = summary.to_vec();
+    lines.push(match summary.len() {
+        0 => String::from("No tasks modified"),
+        1 => String::from("1 task modified"),
+        count => format!("{count} tasks modified"),
+    });
+    print_message(config, lines.join("\n"));
+}
+
+pub fn print_added(config: &Config, count: usize) {
+    match count {
+        1 => print_message(config, "1 task added"),
+        count => print_message(config, format!("{count} tasks added")),
+    }
+}
+
+fn parse_tag(tag: &str) -> Result<String, String> {
+    let tag = tag.trim();
+    if tag.is_empty()
+        || !tag
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(format!("Invalid tag: {tag}"));
+    }
+
+    Ok(tag.to_owned())
+}
+
+fn display_option<T: ToString>(value: &Option<T>) -> String {
+    value
+        .as_ref()
+        .map_or(String::from("none"), |value| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(text: &str) -> Vec<String> {
+        text.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn each_kind_of_change_is_parsed() {
+        let parse = |input: &str| Change::parse(input).unwrap();
+
+        assert_eq!(
+            parse("content:Ship it"),
+            Change::Content(String::from("Ship it"))
+        );
+        assert_eq!(
+            parse("/staging/production/"),
+            Change::Replace(String::from("staging"), String::from("production"))
+        );
+        assert_eq!(
+            parse("/todo//"),
+            Change::Replace(String::from("todo"), String::new())
+        );
+        assert_eq!(parse("status:done"), Change::Done(true));
+        assert_eq!(parse("status:Open"), Change::Done(false));
+        assert_eq!(parse("+urgent"), Change::AddTag(String::from("urgent")));
+        assert_eq!(parse("-urgent"), Change::RemoveTag(String::from("urgent")));
+        assert_eq!(
+            parse("tags:a,b"),
+            Change::Tags(vec![String::from("a"), String::from("b")])
+        );
+        assert_eq!(parse("tags:"), Change::Tags(vec![]));
+        assert_eq!(parse("priority:h"), Change::Priority(Some(Priority::H)));
+        assert_eq!(parse("pri:"), Change::Priority(None));
+        assert_eq!(
+            parse("due:2026-10-30"),
+            Change::Due(Some(String::from("2026-10-30")))
+        );
+        assert_eq!(parse("due:"), Change::Due(None));
+    }
+
+    #[test]
+    fn invalid_changes_are_explained() {
+        let error = |input: &str| Change::parse(input).unwrap_err();
+
+        assert_eq!(
+            error("--dry-run"),
+            "Unknown change: --dry-run, put the options before the ids"
+        );
+        assert_eq!(error("content:"), "The content can't be empty");
+        assert_eq!(
+            error("//new/"),
+            "Invalid replacement: //new/, use /old/new/"
+        );
+        assert_eq!(
+            error("status:later"),
+            "Invalid status: later, use open or done"
+        );
+        assert_eq!(error("+two words"), "Invalid tag: two words");
+        assert_eq!(error("priority:Z"), "Invalid priority: Z, use H, M or L");
+        assert_eq!(error("owner:me"), "Unknown change: owner:me");
+        assert_eq!(error("urgent"), "Unknown change: urgent");
+    }
+
+    #[test]
+    fn new_tasks_split_the_inline_fields_from_the_content() {
+        let task = NewTask::parse("Deploy the API +ops priority:H due:2026-10-30").unwrap();
+
+        assert_eq!(task.content, "Deploy the API");
+        assert_eq!(
+            task.changes,
+            vec![
+                Change::AddTag(String::from("ops")),
+                Change::Priority(Some(Priority::H)),
+                Change::Due(Some(String::from("2026-10-30"))),
+            ]
+        );
+
+        let task = NewTask::parse("Call bob:tomorrow + - C++").unwrap();
+        assert_eq!(task.content, "Call bob:tomorrow + - C++");
+        assert!(task.changes.is_empty());
+
+        assert_eq!(
+            NewTask::parse("+ops priority:H").unwrap_err(),
+            "The content can't be empty: +ops priority:H"
+        );
+        assert_eq!(
+            NewTask::parse("Deploy priority:urgent").unwrap_err(),
+            "Invalid priority: urgent, use H, M or L"
+        );
+    }
+
+    #[test]
+    fn new_tasks_apply_their_fields_over_the_context_defaults() {
+        let mut ctx = Context::new("work", 1);
+        ctx.meta.default_priority = Some(Priority::L);
+        ctx.meta.default_tags = vec![String::from("job")];
+
+        let task = NewTask::parse("Deploy +ops priority:H")
+            .unwrap()
+            .to_task(&ctx, 4);
+
+        assert_eq!(task.id, 4);
+        assert_eq!(task.priority, Some(Priority::H));
+        assert_eq!(task.tags, vec![String::from("job"), String::from("ops")]);
+    }
+
+    #[test]
+    fn where_is_taken_out_of_the_changes() {
+        assert_eq!(
+            split_where(words("+urgent --where tag:deploy -old"), None),
+            Ok((words("+urgent -old"), Some(String::from("tag:deploy"))))
+        );
+        assert_eq!(
+            split_where(words("-w status:open priority:H"), None),
+            Ok((words("priority:H"), Some(String::from("status:open"))))
+        );
+        assert_eq!(
+            split_where(words("priority:H --where=tag:a"), None),
+            Ok((words("priority:H"), Some(String::from("tag:a"))))
+        );
+        assert_eq!(
+            split_where(words("-urgent"), Some(String::from("tag:a"))),
+            Ok((words("-urgent"), Some(String::from("tag:a"))))
+        );
+    }
+
+    #[test]
+    fn invalid_where_options_are_errors() {
+        assert_eq!(
+            split_where(words("+urgent --where"), None),
+            Err(String::from("Missing filter after --where"))
+        );
+        assert_eq!(
+            split_where(words("+urgent -w tag:b"), Some(String::from("tag:a"))),
+            Err(String::from("Only one --where filter can be given"))
+        );
+        assert_eq!(
+            split_where(words("--where tag:a"), None),
+            Err(String::from("No changes given"))
+        );
+    }
+}
@@ -0,0 +1,391 @@
+use clap::ValueEnum;
+use comfy_table::modifiers::UTF8_ROUND_CORNERS;
+use comfy_table::presets::UTF8_FULL;
+use comfy_table::Table;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::Mutex;
+
+use crate::errors::{report, Error};
+use crate::history::pair_tasks;
+use crate::structs::{Config, Context, ContextCountTask, ListOptions, Priority, Service, Task};
+use crate::utils::{capture_output, get_context_index, sort_tasks};
+
+/// The contexts before and after each write of the command, listed by `print_changes`.
+static WRITES: Mutex<Vec<(Vec<Context>, Vec<Context>)>> = Mutex::new(vec![]);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum Format {
+    /// Tables and messages for humans
+    #[default]
+    Table,
+    /// A JSON array of records
+    Json,
+    /// One JSON record per line
+    Jsonl,
+    /// A YAML list of records
+    Yaml,
+    /// Comma-separated values with a header line
+    Csv,
+    /// Tab-separated values with a header line
+    Tsv,
+}
+
+impl Format {
+    /// Mutating commands print the items they changed with these formats.
+    pub fn is_json(self) -> bool {
+        matches!(self, Format::Json | Format::Jsonl)
+    }
+}
+
+/// A task as printed by `--format`, its fields and their order are the documented schema.
+#[derive(Debug, Serialize, Default, Clone, PartialEq)]
+pub struct TaskRecord {
+    pub context: String,
+    pub context_id: usize,
+    /// Position of the task in its context, the id given to the commands
+    pub id: usize,
+    pub content: String,
+    pub done: bool,
+    pub priority: Option<Priority>,
+    pub tags: Vec<String>,
+    pub due: Option<String>,
+    pub created: String,
+    pub modified: String,
+}
+
+impl TaskRecord {
+    fn new(ctx: &Context, id: usize, task: &Task) -> Self {
+        Self {
+            context: ctx.name.clone(),
+            context_id: ctx.id,
+            id,
+            content: task.content.clone(),
+            done: task.done,
+            priority: task.priority,
+            tags: task.tags.clone(),
+            due: task.due.clone(),
+            created: task.creation_date.clone(),
+            modified: task.modification_date.clone(),
+        }
+    }
+}
+
+/// A context as printed by `--format`, `tasks` being its number of tasks.
+#[derive(Debug, Serialize, Default, Clone, PartialEq)]
+pub struct ContextRecord {
+    pub id: usize,
+    pub name: String,
+    pub active: bool,
+    pub archived: bool,
+    pub description: String,
+    pub color: String,
+    pub tasks: usize,
+}
+
+impl From<&Context> for ContextRecord {
+    fn from(ctx: &Context) -> Self {
+        Self {
+            id: ctx.id,
+            name: ctx.name.clone(),
+            active: ctx.active,
+            archived: ctx.archived,
+            description: ctx.meta.description.clone(),
+            color: ctx.meta.color.clone(),
+            tasks: ctx.tasks.len(),
+        }
+    }
+}
+
+impl From<&ContextCountTask> for ContextRecord {
+    fn from(ctx: &ContextCountTask) -> Self {
+        Self {
+            id: ctx.id,
+            name: ctx.name.clone(),
+            active: ctx.active,
+            archived: ctx.archived,
+            description: ctx.description.clone(),
+            color: String::new(),
+            tasks: ctx.task_count as usize,
+        }
+    }
+}
+
+/// An item changed by a command: `created`, `updated` or `deleted`.
+#[derive(Debug, Serialize)]
+struct Changed<T> {
+    change: &'static str,
+    #[serde(flatten)]
+    item: T,
+}
+
+#[derive(Debug, Serialize)]
+struct Changes {
+    tasks: Vec<Changed<TaskRecord>>,
+    contexts: Vec<Changed<ContextRecord>>,
+    /// What the command printed, without --format
+    messages: Vec<String>,
+}
+
+/// Prints the tasks of the list with the filter and the sort, the groups being left to the reader.
+pub fn print_task_records(format: Format, contexts: &[Context], options: &ListOptions) {
+    let mut records = vec![];
+
+    for ctx in contexts {
+        let mut tasks: Vec<Task> = positioned(ctx)
+            .filter(|task| options.filter.as_ref().is_none_or(|f| f.matches(task, ctx)))
+            .collect();
+
+        if let Some(sort) = options.sort {
+            sort_tasks(&mut tasks, sort);
+        }
+
+        records.extend(tasks.iter().map(|task| TaskRecord::new(ctx, task.id, task)));
+    }
+
+    print_records(format, &records);
+}
+
+pub fn print_search_records(format: Format, contexts: &[Context], search: &Regex) {
+    let records: Vec<TaskRecord> = contexts
+        .iter()
+        .flat_map(|ctx| {
+            positioned(ctx)
+                .filter(|task| search.is_match(&task.content))
+                .map(|task| TaskRecord::new(ctx, task.id, &task))
+        })
+        .collect();
+
+    print_records(format, &records);
+}
+
+/// Prints every field of a task of the context, as a two-column table by default.
+pub fn show_task(config: &Config, service: &impl Service, id: usize) {
+    let record = service.get_contexts(config).and_then(|data| {
+        let ctx = &data[get_context_index(&data, config)?];
+        let task = id
+            .checked_sub(1)
+            .and_then(|index| ctx.tasks.get(index))
+            .ok_or(Error::not_found(format!(
+                "No task found with this ID: {id}"
+            )))?;
+
+        Ok(TaskRecord::new(ctx, id, task))
+    });
+
+    let record = match record {
+        Ok(record) => record,
+        Err(err) => {
+            report(err);
+            return;
+        }
+    };
+
+    if config.format != Format::Table {
+        print_records(config.format, &[record]);
+        return;
+    }
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS);
+    if let Value::Object(fields) = to_value(&record) {
+        for (key, value) in &fields {
+            table.add_row(vec![key.to_owned(), cell(value)]);
+        }
+    }
+
+    println!("{table}");
+}
+
+/// Keeps what a write changed for `print_changes`, `before` and `after` being the contexts
+/// it touched as the service loaded and saved them.
+pub fn record_changes(config: &Config, before: &[Context], after: &[Context]) {
+    if config.format.is_json() {
+        WRITES
+            .lock()
+            .expect("Error when recording changes")
+            .push((before.to_vec(), after.to_vec()));
+    }
+}
+
+/// Runs a mutating command and prints the tasks and contexts it wrote as JSON,
+/// with the messages it printed.
+pub fn print_changes(config: &Config, action: impl FnOnce()) {
+    let messages = capture_output(action);
+    let writes = std::mem::take(&mut *WRITES.lock().expect("Error when recording changes"));
+
+    let mut changes = Changes {
+        tasks: vec![],
+        contexts: vec![],
+        messages,
+    };
+    for (before, after) in &writes {
+        changes.tasks.extend(diff_tasks(before, after));
+        changes.contexts.extend(diff_contexts(before, after));
+    }
+
+    let json = match config.format {
+        Format::Jsonl => serde_json::to_string(&changes),
+        _ => serde_json::to_string_pretty(&changes),
+    };
+    println!("{}", json.expect("Error when stringifying changes"));
+}
+
+pub fn print_records<T: Serialize + Default>(format: Format, records: &[T]) {
+    match format {
+        Format::Json | Format::Table => println!(
+            "{}",
+            serde_json::to_string_pretty(records).expect("Error when stringifying records")
+        ),
+        Format::Jsonl => {
+            for record in records {
+                println!("{}", to_value(record));
+            }
+        }
+        Format::Yaml => print_yaml(records),
+        Format::Csv | Format::Tsv => print_separated(format, records),
+    }
+}
+
+/// YAML with the values written as JSON, which YAML reads as flow scalars and sequences.
+fn print_yaml<T: Serialize>(records: &[T]) {
+    if records.is_empty() {
+        println!("[]");
+        return;
+    }
+
+    for record in records {
+        let Value::Object(fields) = to_value(record) else {
+            continue;
+        };
+        for (index, (key, value)) in fields.iter().enumerate() {
+            let prefix = if index == 0 { "- " } else { "  " };
+            println!("{prefix}{key}: {value}");
+        }
+    }
+}
+
+/// The header comes from the default record so it is printed even without records.
+fn print_separated<T: Serialize + Default>(format: Format, records: &[T]) {
+    let separator = if format == Format::Csv { "," } else { "\t" };
+    let escape = |text: String| match format {
+        Format::Csv if text.contains([',', '"', '\n', '\r']) => {
+            format!("\"{}\"", text.replace('"', "\"\""))
+        }
+        Format::Tsv => text.replace(['\t', '\n', '\r'], " "),
+        _ => text,
+    };
+
+    if let Value::Object(fields) = to_value(&T::default()) {
+        let header: Vec<String> = fields.keys().cloned().collect();
+        println!("{}", header.join(separator));
+    }
+
+    for record in records {
+        let Value::Object(fields) = to_value(record) else {
+            continue;
+        };
+        let row: Vec<String> = fields.values().map(|value| escape(cell(value))).collect();
+        println!("{}", row.join(separator));
+    }
+}
+
+/// Text of a value in a table cell, the tags being separated by commas.
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        Value::Array(items) => items.iter().map(cell).collect::<Vec<_>>().join(","),
+        value => value.to_string(),
+    }
+}
+
+fn to_value<T: Serialize>(record: &T) -> Value {
+    serde_json::to_value(record).expect("Error when stringifying record")
+}
+
+/// The tasks of the context with their position as id.
+pub fn positioned(ctx: &Context) -> impl Iterator<Item = Task> + '_ {
+    ctx.tasks.iter().enumerate().map(|(index, task)| Task {
+        id: index + 1,
+        ..task.clone()
+    })
+}
+
+/// The tasks created, updated and deleted, paired in each context like the history does.
+fn diff_tasks(before: &[Context], after: &[Context]) -> Vec<Changed<TaskRecord>> {
+    let empty = |ctx: &Context| Context {
+        tasks: vec![],
+        ..ctx.clone()
+    };
+    let mut changes = vec![];
+
+    for ctx in after {
+        let old = match before.iter().find(|old| old.id == ctx.id) {
+            Some(old) => old.clone(),
+            None => empty(ctx),
+        };
+        changes.extend(diff_context_tasks(&old, ctx));
+    }
+
+    for old in before {
+        if !after.iter().any(|ctx| ctx.id == old.id) {
+            changes.extend(diff_context_tasks(old, &empty(old)));
+        }
+    }
+
+    changes
+}
+
+fn diff_context_tasks(before: &Context, after: &Context) -> Vec<Changed<TaskRecord>> {
+    let record = |ctx: &Context, index: usize| TaskRecord::new(ctx, index + 1, &ctx.tasks[index]);
+
+    pair_tasks(&before.tasks, &after.tasks)
+        .into_iter()
+        .filter_map(|pair| match pair {
+            (None, Some(new)) => Some(("created", record(after, new))),
+            (Some(old), None) => Some(("deleted", record(before, old))),
+            (Some(old), Some(new)) if record(before, old) != record(after, new) => {
+                Some(("updated", record(after, new)))
+            }
+            _ => None,
+        })
+        .map(|(change, item)| Changed { change, item })
+        .collect()
+}
+
+/// The contexts created, updated and deleted, the number of tasks changing with the tasks
+/// already listed.
+fn diff_contexts(before: &[Context], after: &[Context]) -> Vec<Changed<ContextRecord>> {
+    let record = |ctx: &Context| ContextRecord {
+        tasks: 0,
+        ..ContextRecord::from(ctx)
+    };
+    let mut changes = vec![];
+
+    for ctx in after {
+        let change = match before.iter().find(|old| old.id == ctx.id) {
+            None => "created",
+            Some(old) if record(old) != record(ctx) => "updated",
+            Some(_) => continue,
+        };
+        changes.push(Changed {
+            change,
+            item: ContextRecord::from(ctx),
+        });
+    }
+
+    for old in before {
+        if !after.iter().any(|ctx| ctx.id == old.id) {
+            changes.push(Changed {
+                change: "deleted",
+                item: ContextRecord::from(old),
+            });
+        }
+    }
+
+    changes
+}
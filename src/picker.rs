@@ -0,0 +1,294 @@
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use ratatui::backend::{Backend, ClearType, CrosstermBackend};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::crossterm::terminal;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Modifier, Style};
+use ratatui::widgets::{List, ListItem, ListState, Paragraph};
+use ratatui::{Frame, Terminal, TerminalOptions, Viewport};
+use std::io::{self, IsTerminal, Stderr, Write};
+
+use crate::errors::{report, report_as, Error, TaskError};
+use crate::structs::{Config, Service};
+use crate::utils::{format_task, get_context_index};
+
+const HEIGHT: u16 = 12;
+
+type PickerTerminal = Terminal<CrosstermBackend<Stderr>>;
+
+/// Entry of the picker, `value` being what is given to the command.
+struct Item {
+    value: String,
+    label: String,
+}
+
+struct Picker {
+    items: Vec<Item>,
+    multi: bool,
+    query: String,
+    marked: Vec<usize>,
+    state: ListState,
+}
+
+/// Commands run without ids open the picker only when a user can answer it. It draws
+/// on stderr, stdout being left to the output even when it is piped.
+pub fn is_interactive() -> bool {
+    io::stdin().is_terminal() && io::stderr().is_terminal()
+}
+
+/// Lets the user pick tasks of the context, returning their ids separated by commas.
+pub fn pick_tasks(config: &Config, service: &impl Service, multi: bool) -> Option<String> {
+    let items = service.get_contexts(config).and_then(|data| {
+        let index = get_context_index(&data, config)?;
+        let items: Vec<Item> = data[index]
+            .tasks
+            .iter()
+            .enumerate()
+            .map(|(position, task)| Item {
+                value: (position + 1).to_string(),
+                label: format!(
+                    "{:>3} {} {}",
+                    position + 1,
+                    if task.done { "[X]" } else { "[ ]" },
+                    format_task(task)
+                ),
+            })
+            .collect();
+
+        Ok(items)
+    });
+
+    let values = run_picker(items, multi)?;
+    Some(values.join(","))
+}
+
+/// Lets the user pick a context that is not archived.
+pub fn pick_context(config: &Config, service: &impl Service) -> Option<String> {
+    let items = service.get_contexts(config).map(|data| {
+        data.into_iter()
+            .filter(|ctx| !ctx.archived)
+            .map(|ctx| Item {
+                label: format!(
+                    "{}{} ({} tasks)",
+                    if ctx.active { "* " } else { "  " },
+                    ctx.name,
+                    ctx.tasks.len()
+                ),
+                value: ctx.name,
+            })
+            .collect()
+    });
+
+    run_picker(items, false)?.into_iter().next()
+}
+
+/// Asks a line on stdin, the label being printed on stderr to keep stdout for the output.
+pub fn prompt(label: &str) -> Option<String> {
+    eprint!("{label}");
+    io::stderr().flush().ok()?;
+
+    let mut line = String::new();
+    io::stdin().read_line(&mut line).ok()?;
+
+    let line = line.trim();
+    (!line.is_empty()).then(|| line.to_owned())
+}
+
+fn run_picker(items: Result<Vec<Item>, Error>, multi: bool) -> Option<Vec<String>> {
+    let items = match items {
+        Ok(items) if items.is_empty() => {
+            eprintln!("Nothing to pick from");
+            return None;
+        }
+        Ok(items) => items,
+        Err(err) => {
+            report(err);
+            return None;
+        }
+    };
+
+    let mut picker = Picker {
+        items,
+        multi,
+        query: String::new(),
+        marked: vec![],
+        state: ListState::default().with_selected(Some(0)),
+    };
+
+    let mut terminal = match open_terminal() {
+        Ok(terminal) => terminal,
+        Err(err) => {
+            let _ = terminal::disable_raw_mode();
+            report_as(
+                TaskError::Storage,
+                format!("Error when opening the picker: {}", err),
+            );
+            return None;
+        }
+    };
+    let res = picker.run(&mut terminal);
+    // Cleared by hand as `Terminal::clear` asks the cursor position too
+    let top = terminal.get_frame().area().y;
+    let _ = terminal.set_cursor_position((0, top));
+    let _ = terminal.backend_mut().clear_region(ClearType::AfterCursor);
+    let _ = terminal.show_cursor();
+    let _ = terminal::disable_raw_mode();
+
+    match res {
+        Ok(selection) => selection,
+        Err(err) => {
+            report_as(TaskError::Storage, err);
+            None
+        }
+    }
+}
+
+/// The picker draws on stderr, stdout being kept for the output of the command. It takes the
+/// bottom of the screen, an inline viewport asking the cursor position on stdout.
+fn open_terminal() -> io::Result<PickerTerminal> {
+    terminal::enable_raw_mode()?;
+
+    let (width, rows) = terminal::size()?;
+    let height = HEIGHT.min(rows);
+    let mut stderr = io::stderr();
+    // Scrolls the screen so the lines above the picker stay visible
+    stderr.write_all("\n".repeat(height as usize).as_bytes())?;
+    stderr.flush()?;
+
+    let options = TerminalOptions {
+        viewport: Viewport::Fixed(Rect::new(0, rows - height, width, height)),
+    };
+    Terminal::with_options(CrosstermBackend::new(stderr), options)
+}
+
+impl Picker {
+    /// Returns the picked values, none when cancelled.
+    fn run(&mut self, terminal: &mut PickerTerminal) -> io::Result<Option<Vec<String>>> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            match key.code {
+                KeyCode::Esc => return Ok(None),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(None)
+                }
+                KeyCode::Enter => return Ok(self.selection()),
+                KeyCode::Down => self.state.select_next(),
+                KeyCode::Up => self.state.select_previous(),
+                KeyCode::Tab if self.multi => self.toggle_mark(),
+                KeyCode::Backspace => {
+                    self.query.pop();
+                    self.state.select(Some(0));
+                }
+                KeyCode::Char(c) => {
+                    self.query.push(c);
+                    self.state.select(Some(0));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Indexes of the items matching the query, best matches first.
+    fn matches(&self) -> Vec<usize> {
+        if self.query.is_empty() {
+            return (0..self.items.len()).collect();
+        }
+
+        let matcher = SkimMatcherV2::default().ignore_case();
+        let mut scored: Vec<(i64, usize)> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(index, item)| {
+                matcher
+                    .fuzzy_match(&item.label, &self.query)
+                    .map(|score| (score, index))
+            })
+            .collect();
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+
+        scored.into_iter().map(|(_, index)| index).collect()
+    }
+
+    fn current(&self) -> Option<usize> {
+        self.state
+            .selected()
+            .and_then(|selected| self.matches().get(selected).copied())
+    }
+
+    fn toggle_mark(&mut self) {
+        let Some(index) = self.current() else {
+            return;
+        };
+
+        match self.marked.iter().position(|marked| *marked == index) {
+            Some(position) => {
+                self.marked.remove(position);
+            }
+            None => self.marked.push(index),
+        }
+        self.state.select_next();
+    }
+
+    /// The marked items, or the current one when none is marked.
+    fn selection(&self) -> Option<Vec<String>> {
+        let mut indexes = if self.marked.is_empty() {
+            vec![self.current()?]
+        } else {
+            self.marked.clone()
+        };
+        indexes.sort();
+
+        Some(
+            indexes
+                .into_iter()
+                .map(|index| self.items[index].value.clone())
+                .collect(),
+        )
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [list_area, prompt_area] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+
+        let matches = self.matches();
+        let items: Vec<ListItem> = matches
+            .iter()
+            .map(|index| {
+                let marker = if self.marked.contains(index) {
+                    "* "
+                } else {
+                    "  "
+                };
+                ListItem::new(format!("{marker}{}", self.items[*index].label))
+            })
+            .collect();
+        frame.render_stateful_widget(
+            List::new(items).highlight_style(Style::default().add_modifier(Modifier::REVERSED)),
+            list_area,
+            &mut self.state,
+        );
+
+        let help = if self.multi {
+            "tab to select several, enter to confirm, esc to cancel"
+        } else {
+            "enter to confirm, esc to cancel"
+        };
+        let prompt = format!(
+            "> {}  ({}/{}) {help}",
+            self.query,
+            matches.len(),
+            self.items.len()
+        );
+        frame.render_widget(Paragraph::new(prompt), prompt_area);
+    }
+}
@@ -0,0 +1,125 @@
+use serde::Deserialize;
+use std::env;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::errors::Error;
+use crate::structs::{Config, Context};
+use crate::utils::get_remote_path;
+
+const PREFIX: &str = "task-";
+
+/// Line printed by a plugin on stdout to run a command through the normal write path,
+/// e.g. `{"command": ["done", "3"]}`.
+#[derive(Debug, Deserialize)]
+struct Mutation {
+    command: Vec<String>,
+}
+
+/// Looks for a `task-{name}` executable in the PATH directories.
+pub fn find_plugin(name: &str) -> Option<PathBuf> {
+    let paths = env::var_os("PATH")?;
+
+    env::split_paths(&paths)
+        .map(|dir| dir.join(format!("{PREFIX}{name}")))
+        .find(|path| {
+            path.metadata()
+                .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        })
+}
+
+/// Whether the plugin gets the data as JSON on its stdin, set in its config.
+pub fn plugin_stdin(config: &Config, path: &Path) -> bool {
+    config
+        .plugins
+        .get(plugin_name(path).trim_start_matches(PREFIX))
+        .is_some_and(|plugin| plugin.stdin)
+}
+
+fn plugin_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Runs the plugin with the config in environment variables and the contexts on its stdin
+/// when given, its stdout is printed and the mutations it contains are returned to be
+/// applied once it succeeded.
+pub fn run_plugin(
+    config: &Config,
+    path: &PathBuf,
+    args: &[String],
+    contexts: Option<Vec<Context>>,
+) -> Result<Vec<Vec<String>>, Error> {
+    let name = plugin_name(path);
+
+    // Without the data, the active context is left to the plugin
+    let active = contexts
+        .iter()
+        .flatten()
+        .find(|ctx| ctx.active)
+        .map(|ctx| &ctx.name);
+    let context = config.context.as_ref().or(active).cloned();
+
+    let data_path = if config.ssh_ip.is_empty() {
+        config.local_file_path.clone()
+    } else {
+        get_remote_path(config)
+    };
+
+    let mut child = Command::new(path)
+        .args(args)
+        .env("TASK_DATA_PATH", data_path)
+        .env("TASK_SSH_IP", &config.ssh_ip)
+        .env("TASK_SSH_USERNAME", &config.ssh_username)
+        .env("TASK_API_URL", &config.api_url)
+        .env("TASK_CONTEXT", context.unwrap_or_default())
+        .stdin(match contexts {
+            Some(_) => Stdio::piped(),
+            None => Stdio::inherit(),
+        })
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|err| Error::storage(format!("Error when running {name}: {err}")))?;
+
+    // Written from another thread, as the plugin may fill its stdout before reading it all
+    let writer = child
+        .stdin
+        .take()
+        .zip(contexts)
+        .map(|(mut input, contexts)| {
+            thread::spawn(move || {
+                let json = serde_json::to_string(&contexts).expect("Error when stringifying data");
+                // The plugin may exit without reading its input
+                let _ = input.write_all(json.as_bytes());
+            })
+        });
+
+    let mut mutations = vec![];
+    if let Some(output) = child.stdout.take() {
+        for line in BufReader::new(output).lines().map_while(Result::ok) {
+            match serde_json::from_str::<Mutation>(&line) {
+                Ok(mutation) => mutations.push(mutation.command),
+                Err(_) => println!("{line}"),
+            }
+        }
+    }
+
+    if let Some(writer) = writer {
+        let _ = writer.join();
+    }
+
+    let status = child
+        .wait()
+        .map_err(|err| Error::storage(format!("Error when running {name}: {err}")))?;
+    if !status.success() {
+        return Err(Error::storage(format!(
+            "{name} failed ({status}), no changes applied"
+        )));
+    }
+
+    Ok(mutations)
+}
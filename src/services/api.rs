@@ -1,144 +1,277 @@
+use crate::confirm::count_tasks;
+use crate::errors::{report, report_as, Error, TaskError};
+use crate::filter::Filter;
+use crate::modify::{print_added, Change, NewTask};
+use crate::output::{print_records, record_changes, ContextRecord, Format};
 use crate::structs::{
-    Config, Context, ContextCountTask, ContextOnly, ContextRequest, ContextUpdateRequest, Service,
-    Task, TaskDoneRequest, TaskRequest, TaskRequestFull,
+    Config, Context, ContextCountTask, ContextMeta, ContextOnly, ContextRequest,
+    ContextUpdateRequest, ListOptions, Service, TaskRequest, TaskRequestFull,
 };
-use crate::utils::{get_or_create_data_file, get_or_create_data_file_ssh, print_tasks};
-#[allow(dead_code, unused_variables)]
+use crate::utils::{
+    build_search_regex, get_or_create_data_file, get_or_create_data_file_ssh, parse_args,
+    print_list, print_message, print_search, print_tasks, repair_context_ids, select_ids,
+};
+use chrono::NaiveDateTime;
 use comfy_table::modifiers::UTF8_ROUND_CORNERS;
 use comfy_table::presets::UTF8_FULL;
 use comfy_table::Table;
-use reqwest::blocking::{Client, Response};
-use reqwest::{header, Error as ReqwestErr};
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::{header, Error as ReqwestErr, Method, StatusCode};
+use serde::de::DeserializeOwned;
+use std::slice;
 
 #[derive(Debug)]
 pub struct ApiService;
 
 impl Service for ApiService {
     fn use_context(&self, config: &Config, name: String) {
-        let client = get_client(&config).expect("Error when creating http client");
+        let Some(client) = client(config) else {
+            return;
+        };
         let body = ContextRequest {
             name,
             active: false,
             simple_create: false,
+            meta: ContextMeta::default(),
         };
 
-        let data: Context = client
-            .post(get_url(&config, "context"))
-            .json(&body)
-            .send()
-            .expect("Error when creating context")
-            .json()
-            .expect("Error when parsing response");
+        let request = client.post(get_url(config, "context")).json(&body);
+        let Some(data) = send(config, request, "Error when creating context").and_then(read_json)
+        else {
+            return;
+        };
 
         let updated = mutate_tasks_ids(vec![data]);
 
-        print_tasks(&config, &updated[0]);
+        if !config.quiet {
+            print_tasks(config, &updated[0]);
+        }
     }
 
     fn add_task(&self, config: &Config, content: String) {
-        let client = get_client(&config).expect("Error when creating http client");
+        let Some(client) = client(config) else {
+            return;
+        };
+
+        let request = client.post(get_url(config, "task"));
+        let request = request.json(&TaskRequest { content });
+        if send(config, request, "Error when creating task").is_none() {
+            return;
+        }
+
+        print_message(config, "Task created");
+    }
+
+    fn add_tasks(&self, config: &Config, tasks: Vec<NewTask>) {
+        let Some(client) = client(config) else {
+            return;
+        };
+
+        let contexts = match get_tasks(config, &client, false) {
+            Ok(contexts) => contexts,
+            Err(err) => {
+                report(err);
+                return;
+            }
+        };
+        let Some(ctx) = contexts.first() else {
+            report_as(
+                TaskError::NotFound,
+                "No current active context, let's create one using task use {name}",
+            );
+            return;
+        };
+
+        let mut updated = ctx.clone();
+        for new_task in tasks {
+            let task = new_task.to_task(ctx, updated.tasks.len() + 1);
+            updated.tasks.push(task);
+        }
+
+        let body: Vec<TaskRequestFull> = updated.tasks[ctx.tasks.len()..]
+            .iter()
+            .map(|task| TaskRequestFull {
+                content: task.content.clone(),
+                done: false,
+                context_id: ctx.id as i32,
+                creation_date: task.creation_date.clone(),
+                modification_date: task.modification_date.clone(),
+                priority: task.priority,
+                tags: task.tags.clone(),
+                due: task.due.clone(),
+            })
+            .collect();
+
+        let request = client.post(get_url(config, "task/batch")).json(&body);
+        if send(config, request, "Error when creating tasks").is_none() {
+            return;
+        }
 
-        let _data: Task = client
-            .post(get_url(&config, "task"))
-            .json(&TaskRequest { content })
-            .send()
-            .expect("Error when creating context")
-            .json()
-            .expect("Error when parsing response");
+        record_changes(config, slice::from_ref(ctx), &[updated]);
 
-        println!("Task created");
+        print_added(config, body.len());
     }
 
-    fn edit_context(&self, config: &Config, index: usize, name: String) {
-        let client = get_client(&config).expect("Error when creating http client");
+    fn edit_context(&self, config: &Config, name: String, new_name: String) {
+        let Some(client) = client(config) else {
+            return;
+        };
 
-        let res: Response = client
-            .put(get_url(&config, &format!("context/index/{}", index)))
-            .json(&ContextUpdateRequest { name: name.clone() })
-            .send()
-            .expect("Error when fetching contexts");
+        // Contexts are only found by their position in lsc
+        let Ok(index) = name.parse::<usize>() else {
+            report_as(
+                TaskError::InvalidInput,
+                "Renaming a context by name is not supported with the API, give its index",
+            );
+            return;
+        };
 
-        if res.status().is_success() {
-            println!("Context updated with name: {}", name);
-        } else {
-            println!("Error when updating context, status: {}", res.status());
+        let url = get_url(config, &format!("context/index/{}", index));
+        let request = client.put(url).json(&ContextUpdateRequest {
+            name: new_name.clone(),
+        });
+        if send(config, request, "Error when updating context").is_none() {
+            return;
         }
+
+        print_message(config, format!("Context updated with name: {}", new_name));
     }
 
-    fn edit_task(&self, config: &Config, id: usize, content: String) {
-        let client = get_client(&config).expect("Error when creating http client");
+    fn edit_task(&self, config: &Config, id: String, content: String) {
+        let Some(client) = client(config) else {
+            return;
+        };
+
+        let Some((ctx, ids)) = resolve_ids(config, &client, id, None) else {
+            return;
+        };
 
-        let res: Response = client
-            .put(get_url(&config, &format!("task/{}?index=true", id)))
+        let request = client
+            .put(get_url(
+                config,
+                &format!("task/{}?index=true", join_ids(&ids)),
+            ))
             .json(&TaskRequest {
                 content: content.clone(),
-            })
-            .send()
-            .expect("Error when fetching contexts");
+            });
+        if send(config, request, "Error when updating task").is_none() {
+            return;
+        }
 
-        if res.status().is_success() {
-            println!("Task updated with content: {}", content);
-        } else {
-            println!("Error when updating task, status: {}", res.status());
+        let mut updated = ctx.clone();
+        for task in updated
+            .tasks
+            .iter_mut()
+            .filter(|task| ids.contains(&task.id))
+        {
+            task.content = content.clone();
         }
+        record_changes(config, &[ctx], &[updated]);
+
+        print_message(config, format!("Task updated with content: {}", content));
     }
 
-    fn del_context(&self, config: &Config, index: String) {
-        let client = get_client(&config).expect("Error when creating http client");
+    fn archive_context(&self, _config: &Config, _name: String, _archived: bool) {
+        report_as(
+            TaskError::InvalidInput,
+            "Archiving contexts is not supported with the API",
+        );
+    }
 
-        let res: Response = client
-            .delete(get_url(&config, &format!("context/{}?index=true", index)))
-            .send()
-            .expect("Error when fetching contexts");
+    fn set_context_meta(&self, _config: &Config, _name: String, _key: String, _value: String) {
+        report_as(
+            TaskError::InvalidInput,
+            "Context metadata is not supported with the API",
+        );
+    }
 
-        if res.status().is_success() {
-            println!("Context deleted");
-        } else {
-            println!("Error when deleting context, status: {}", res.status());
+    fn del_context(&self, config: &Config, index: String) {
+        let Some(client) = client(config) else {
+            return;
+        };
+
+        let request = client.delete(get_url(config, &format!("context/{}?index=true", index)));
+        if send(config, request, "Error when deleting context").is_none() {
+            return;
         }
+
+        print_message(config, "Context deleted");
     }
 
-    fn del_task(&self, config: &Config, id: String) {
-        let client = get_client(&config).expect("Error when creating http client");
+    fn del_task(&self, config: &Config, id: String, filter: Option<Filter>) {
+        let Some(client) = client(config) else {
+            return;
+        };
+
+        let Some((ctx, ids)) = resolve_ids(config, &client, id, filter) else {
+            return;
+        };
 
-        let _data: Vec<Task> = client
-            .delete(get_url(&config, &format!("task/{}?index=true", id)))
-            .send()
-            .expect("Error when fetching contexts")
-            .json()
-            .expect("Error when parsing response");
+        let request = client.delete(get_url(
+            config,
+            &format!("task/{}?index=true", join_ids(&ids)),
+        ));
+        if send(config, request, "Error when deleting tasks").is_none() {
+            return;
+        }
 
-        println!("Tasks deleted");
-    }
+        let mut updated = ctx.clone();
+        updated.tasks.retain(|task| !ids.contains(&task.id));
+        record_changes(config, &[ctx], &[updated]);
 
-    fn list_tasks(&self, config: &Config, all: bool) {
-        let client = get_client(&config).expect("Error when creating http client");
+        print_message(config, "Tasks deleted");
+    }
 
-        let slug = if all { "task" } else { "task?active=true" };
+    fn list_tasks(&self, config: &Config, options: ListOptions) {
+        let Some(client) = client(config) else {
+            return;
+        };
 
-        let data: Vec<Context> = client
-            .get(get_url(&config, slug))
-            .send()
-            .expect("Error when fetching contexts")
-            .json()
-            .expect("Error when parsing response");
+        let all = options.all || !options.contexts.is_empty();
+        let contexts = match get_tasks(config, &client, all) {
+            Ok(contexts) => contexts,
+            Err(err) => {
+                report(err);
+                return;
+            }
+        };
 
-        let updated = mutate_tasks_ids(data);
+        let contexts: Vec<Context> = contexts
+            .into_iter()
+            .filter(|ctx| !options.all || !ctx.archived)
+            .filter(|ctx| {
+                options.contexts.is_empty()
+                    || options
+                        .contexts
+                        .iter()
+                        .any(|name| *name == ctx.name || *name == ctx.id.to_string())
+            })
+            .collect();
 
-        for ctx in &updated {
-            print_tasks(&config, &ctx);
-        }
+        print_list(config, &contexts, &options);
     }
 
-    fn list_contexts(&self, config: &Config) {
-        let client = get_client(&config).expect("Error when creating http client");
+    fn list_contexts(&self, config: &Config, all: bool) {
+        let Some(client) = client(config) else {
+            return;
+        };
+
+        let request = client.get(get_url(config, "context?count=true"));
+        let Some(data) = send(config, request, "Error when fetching contexts")
+            .and_then(read_json::<Vec<ContextCountTask>>)
+        else {
+            return;
+        };
 
-        let data: Vec<ContextCountTask> = client
-            .get(get_url(&config, "context?count=true"))
-            .send()
-            .expect("Error when fetching contexts")
-            .json()
-            .expect("Error when parsing response");
+        if config.format != Format::Table {
+            let records: Vec<ContextRecord> = data
+                .iter()
+                .filter(|ctx| all || !ctx.archived)
+                .map(ContextRecord::from)
+                .collect();
+            print_records(config.format, &records);
+            return;
+        }
 
         let mut table = Table::new();
         table
@@ -146,68 +279,227 @@ impl Service for ApiService {
             .apply_modifier(UTF8_ROUND_CORNERS);
 
         for (i, ctx) in data.iter().enumerate() {
-            let active = if ctx.active { "active" } else { "" };
+            if ctx.archived && !all {
+                continue;
+            }
+
+            let active = if ctx.active {
+                "active"
+            } else if ctx.archived {
+                "archived"
+            } else {
+                ""
+            };
             table.add_row(vec![
                 (i + 1).to_string(),
                 ctx.name.to_owned(),
+                ctx.description.to_owned(),
                 format!("{} tasks", ctx.task_count),
                 active.to_string(),
             ]);
         }
 
-        if data.len() == 0 {
+        if data.is_empty() {
             table.add_row(vec!["Add your first context using: tasks use {{context}}"]);
         }
 
         println!("{table}");
     }
 
-    fn mark_done(&self, config: &Config, index: String) {
-        let client = get_client(&config).expect("Error when creating http client");
+    fn search_tasks(&self, config: &Config, pattern: String, regex: bool) {
+        let search = match build_search_regex(&pattern, regex) {
+            Ok(search) => search,
+            Err(err) => {
+                report_as(TaskError::InvalidInput, err);
+                return;
+            }
+        };
 
-        let res = client
-            .put(get_url(&config, &format!("task/done/{}?index=true", index)))
-            .send()
-            .expect("Error when fetching contexts");
+        let Some(client) = client(config) else {
+            return;
+        };
+        let contexts = match get_tasks(config, &client, true) {
+            Ok(contexts) => contexts,
+            Err(err) => {
+                report(err);
+                return;
+            }
+        };
+
+        print_search(config, &contexts, &search);
+    }
+
+    fn mark_done(&self, config: &Config, index: String, filter: Option<Filter>) {
+        let Some(client) = client(config) else {
+            return;
+        };
+
+        let Some((ctx, ids)) = resolve_ids(config, &client, index, filter) else {
+            return;
+        };
 
-        if res.status().is_success() {
-            println!("Tasks marked as done");
-        } else {
-            println!("Error when marking tasks as done, status: {}", res.status());
+        let request = client.put(get_url(
+            config,
+            &format!("task/done/{}?index=true", join_ids(&ids)),
+        ));
+        if send(config, request, "Error when marking tasks as done").is_none() {
+            return;
         }
+
+        let mut updated = ctx.clone();
+        for task in updated
+            .tasks
+            .iter_mut()
+            .filter(|task| ids.contains(&task.id))
+        {
+            task.done = true;
+        }
+        record_changes(config, &[ctx], &[updated]);
+
+        print_message(config, "Tasks marked as done");
     }
 
-    fn clear_tasks(&self, config: &Config) {
-        let client = get_client(&config).expect("Error when creating http client");
+    fn modify_tasks(
+        &self,
+        _config: &Config,
+        _ids: String,
+        _changes: Vec<Change>,
+        _filter: Option<Filter>,
+    ) {
+        report_as(
+            TaskError::InvalidInput,
+            "Modifying tasks is not supported with the API",
+        );
+    }
 
-        let res: Response = client
-            .post(get_url(&config, "context/clear"))
-            .send()
-            .expect("Error when fetching contexts");
+    fn move_task(&self, _config: &Config, _id: usize, _position: usize) {
+        report_as(
+            TaskError::InvalidInput,
+            "Reordering tasks is not supported with the API",
+        );
+    }
 
-        if res.status().is_success() {
-            println!("Context cleared");
-        } else {
-            println!("Error when clearing context, status: {}", res.status());
+    fn clear_tasks(&self, config: &Config) {
+        let Some(client) = client(config) else {
+            return;
+        };
+
+        let request = client.post(get_url(config, "context/clear"));
+        if send(config, request, "Error when clearing context").is_none() {
+            return;
         }
+
+        print_message(config, "Context cleared");
     }
-}
 
-pub fn migrate(config: &Config) {
-    let data_res = get_file_data(config);
+    fn edit_tasks(&self, _config: &Config, _context: Option<String>) {
+        report_as(
+            TaskError::InvalidInput,
+            "Editing the tasks is not supported with the API",
+        );
+    }
 
-    if data_res.is_err() {
-        return;
+    fn show_log(&self, _config: &Config, _since: Option<NaiveDateTime>) {
+        report_as(
+            TaskError::InvalidInput,
+            "The activity log is not available with the API",
+        );
+    }
+
+    fn undo(&self, _config: &Config, _steps: usize) {
+        report_as(
+            TaskError::InvalidInput,
+            "Undo is not supported with the API",
+        );
+    }
+
+    fn redo(&self, _config: &Config, _steps: usize) {
+        report_as(
+            TaskError::InvalidInput,
+            "Redo is not supported with the API",
+        );
+    }
+
+    fn get_contexts(&self, config: &Config) -> Result<Vec<Context>, Error> {
+        let client = get_client(config)?;
+
+        get_tasks(config, &client, true)
+    }
+
+    fn read_contexts(&self, config: &Config) -> Result<Vec<Context>, Error> {
+        self.get_contexts(config)
+    }
+
+    /// The contexts are deleted by their position in `lsc`, not by their id.
+    fn find_contexts(&self, config: &Config, indexes: &str) -> Result<Vec<ContextRecord>, Error> {
+        let client = get_client(config)?;
+        let data: Vec<ContextCountTask> = fetch(client.get(get_url(config, "context?count=true")))?;
+
+        parse_args(indexes)
+            .iter()
+            .map(|index| {
+                index
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|index| data.get(index.checked_sub(1)?))
+                    .map(ContextRecord::from)
+                    .ok_or(Error::not_found(format!(
+                        "No context found with this index: {}",
+                        index.trim()
+                    )))
+            })
+            .collect()
     }
-    let data = data_res.unwrap();
-    let client = get_client(&config).expect("Error when creating http client");
+}
 
-    let deleted_ctx = client.delete(get_url(&config, "context")).send();
-    let deleted_tasks = client.delete(get_url(&config, "task")).send();
+/// What `migrate` replaces: everything stored on the API, by the content of the data file.
+pub fn migrate_summary(config: &Config) -> Result<String, Error> {
+    if config.api_url.is_empty() {
+        return Err(Error::invalid(
+            "No API configured, set api_url in the config to migrate",
+        ));
+    }
 
-    if deleted_ctx.is_err() || deleted_tasks.is_err() {
-        println!("Error when deleting data from API");
+    let data = get_file_data(config)?;
+    let client = get_client(config)?;
+    let count = |contexts: &[Context]| {
+        let tasks = contexts.iter().map(|ctx| ctx.tasks.len()).sum();
+        format!("{} contexts and {}", contexts.len(), count_tasks(tasks))
+    };
+
+    let stored = client
+        .get(get_url(config, "task"))
+        .send()
+        .and_then(|res| res.json::<Vec<Context>>())
+        .map_or(String::from("every context and task"), |api| {
+            format!("the {}", count(&api))
+        });
+
+    Ok(format!(
+        "This deletes {stored} stored on {}, then uploads the {} of the data file",
+        config.api_url,
+        count(&data)
+    ))
+}
+
+pub fn migrate(config: &Config) {
+    let data = match get_file_data(config) {
+        Ok(data) => data,
+        Err(err) => {
+            report(err);
+            return;
+        }
+    };
+    let Some(client) = client(config) else {
         return;
+    };
+
+    for slug in ["context", "task"] {
+        let request = client.delete(get_url(config, slug));
+        if send(config, request, "Error when deleting data from API").is_none() && !config.dry_run {
+            return;
+        }
     }
 
     for context in data {
@@ -215,61 +507,218 @@ pub fn migrate(config: &Config) {
             name: context.name,
             active: context.active,
             simple_create: true,
+            meta: context.meta,
+        };
+        let request = client.post(get_url(config, "context")).json(&body_ctx);
+        let error = format!("Error when migrating context {}", body_ctx.name);
+        let context_id = match send(config, request, &error).and_then(read_json::<ContextOnly>) {
+            Some(created) => created.id,
+            // With --dry-run the context isn't created, its id stays unknown
+            None if config.dry_run => 0,
+            None => return,
         };
-        let created_ctx = client
-            .post(get_url(&config, "context"))
-            .json(&body_ctx)
-            .send()
-            .expect(format!("Error when migrating context {}", body_ctx.name).as_str())
-            .json::<ContextOnly>()
-            .expect("Error when parsing response");
 
         let tasks: Vec<TaskRequestFull> = context
             .tasks
             .iter()
             .map(|task| TaskRequestFull {
                 content: task.content.clone(),
-                context_id: created_ctx.id as i32,
+                done: task.done,
+                context_id: context_id as i32,
                 creation_date: task.creation_date.clone(),
                 modification_date: task.modification_date.clone(),
+                priority: task.priority,
+                tags: task.tags.clone(),
+                due: task.due.clone(),
             })
             .collect();
 
-        let _created_tasks = client
-            .post(get_url(&config, "task/batch"))
-            .json(&tasks)
-            .send()
-            .expect(format!("Error when migrating tasks for context {}", body_ctx.name).as_str());
+        let request = client.post(get_url(config, "task/batch")).json(&tasks);
+        let error = format!("Error when migrating tasks for context {}", body_ctx.name);
+        if send(config, request, &error).is_none() && !config.dry_run {
+            return;
+        }
+    }
+
+    if !config.dry_run {
+        print_message(config, "Migration completed");
+    }
+}
+
+/// Sends the request, reporting the error message when it fails or the API refuses it.
+/// Returns nothing with --dry-run for the requests changing the data.
+fn send(config: &Config, request: RequestBuilder, error: &str) -> Option<Response> {
+    match try_send(config, request)? {
+        Ok(res) if res.status().is_success() => Some(res),
+        Ok(res) => {
+            report_as(
+                status_error(res.status()),
+                format!("{error}, status: {}", res.status()),
+            );
+            None
+        }
+        Err(err) => {
+            report_as(TaskError::Storage, format!("{error}: {err}"));
+            None
+        }
+    }
+}
+
+/// The kind of error of a refused request, from its status.
+fn status_error(status: StatusCode) -> TaskError {
+    match status {
+        StatusCode::NOT_FOUND => TaskError::NotFound,
+        StatusCode::CONFLICT => TaskError::Conflict,
+        StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY => TaskError::InvalidInput,
+        _ => TaskError::Storage,
+    }
+}
+
+fn read_json<T: DeserializeOwned>(res: Response) -> Option<T> {
+    match res.json() {
+        Ok(data) => Some(data),
+        Err(err) => {
+            report_as(
+                TaskError::Storage,
+                format!("Error when parsing response: {err}"),
+            );
+            None
+        }
+    }
+}
+
+/// Sends the request, or only prints it with --dry-run when it changes the data.
+fn try_send(config: &Config, request: RequestBuilder) -> Option<Result<Response, ReqwestErr>> {
+    let (client, request) = request.build_split();
+    let request = match request {
+        Ok(request) => request,
+        Err(err) => return Some(Err(err)),
+    };
+
+    if !config.dry_run || request.method() == Method::GET {
+        return Some(client.execute(request));
     }
 
-    println!("Migration completed");
+    println!("Would send {} {}", request.method(), request.url());
+    if let Some(body) = request.body().and_then(|body| body.as_bytes()) {
+        let body = serde_json::from_slice::<serde_json::Value>(body)
+            .and_then(|json| serde_json::to_string_pretty(&json))
+            .unwrap_or_else(|_| String::from_utf8_lossy(body).into_owned());
+        println!("{body}");
+    }
+
+    None
 }
 
 fn get_url(config: &Config, slug: &str) -> String {
     format!("{}/{}", config.api_url, slug)
 }
 
-fn get_client(config: &Config) -> Result<Client, ReqwestErr> {
+fn get_tasks(config: &Config, client: &Client, all: bool) -> Result<Vec<Context>, Error> {
+    let request = if all {
+        client.get(get_url(config, "task"))
+    } else {
+        client.get(get_url(config, "task?active=true"))
+    };
+
+    let data: Vec<Context> = fetch(request)?;
+
+    Ok(mutate_tasks_ids(data))
+}
+
+/// Sends a request reading data, the errors keeping the kind of the status.
+fn fetch<T: DeserializeOwned>(request: RequestBuilder) -> Result<T, Error> {
+    let res = request
+        .send()
+        .map_err(|err| Error::storage(format!("Error when fetching contexts: {err}")))?;
+    if !res.status().is_success() {
+        return Err(Error::new(
+            status_error(res.status()),
+            format!("Error when fetching contexts, status: {}", res.status()),
+        ));
+    }
+
+    res.json()
+        .map_err(|err| Error::storage(format!("Error when parsing response: {err}")))
+}
+
+/// Resolves the selector and the filter on the tasks of the context
+/// to get the ids to send to the API, returned with the context.
+fn resolve_ids(
+    config: &Config,
+    client: &Client,
+    ids: String,
+    filter: Option<Filter>,
+) -> Option<(Context, Vec<usize>)> {
+    let contexts = match get_tasks(config, client, false) {
+        Ok(contexts) => contexts,
+        Err(err) => {
+            report(err);
+            return None;
+        }
+    };
+    let Some(ctx) = contexts.into_iter().next() else {
+        report_as(
+            TaskError::NotFound,
+            "No current active context, let's create one using task use {name}",
+        );
+        return None;
+    };
+
+    match select_ids(&ctx, &ids, filter.as_ref()) {
+        Ok(selected) => Some((ctx, selected)),
+        Err(err) => {
+            report(err);
+            None
+        }
+    }
+}
+
+fn join_ids(ids: &[usize]) -> String {
+    let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+    ids.join(",")
+}
+
+/// The commands only target the active context, the API having no way to select another one.
+fn get_client(config: &Config) -> Result<Client, Error> {
+    if config.context.is_some() {
+        return Err(Error::invalid(
+            "Selecting a context with --context is not supported with the API",
+        ));
+    }
+
     let mut headers = header::HeaderMap::new();
-    let mut api_key = header::HeaderValue::from_str(&config.api_key).unwrap();
+    let mut api_key = header::HeaderValue::from_str(&config.api_key)
+        .map_err(|_| Error::invalid("Invalid api_key in the config"))?;
     api_key.set_sensitive(true);
     headers.insert(header::AUTHORIZATION, api_key);
 
-    let client = reqwest::blocking::Client::builder()
+    reqwest::blocking::Client::builder()
         .default_headers(headers)
-        .build()?;
+        .build()
+        .map_err(|err| Error::storage(format!("Error when creating http client: {err}")))
+}
 
-    Ok(client)
+/// The client for the commands, reporting why it can't be created.
+fn client(config: &Config) -> Option<Client> {
+    match get_client(config) {
+        Ok(client) => Some(client),
+        Err(err) => {
+            report(err);
+            None
+        }
+    }
 }
 
-fn get_file_data(config: &Config) -> Result<Vec<Context>, String> {
-    let data_res = if config.ssh_ip.is_empty() {
-        get_or_create_data_file(&config.local_file_path, &config.folder_path, false)
+fn get_file_data(config: &Config) -> Result<Vec<Context>, Error> {
+    let mut data = if config.ssh_ip.is_empty() {
+        get_or_create_data_file(&config.local_file_path, &config.folder_path, false)?
     } else {
-        get_or_create_data_file_ssh(&config, false)
+        get_or_create_data_file_ssh(config, false)?
     };
+    repair_context_ids(&mut data);
 
-    data_res
+    Ok(data)
 }
 
 fn mutate_tasks_ids(contexts: Vec<Context>) -> Vec<Context> {
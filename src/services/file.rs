@@ -1,65 +1,91 @@
-use crate::args::{Cli, Commands};
-use chrono::Local;
-use clap::Parser;
+use chrono::{Local, NaiveDateTime};
 use comfy_table::modifiers::UTF8_ROUND_CORNERS;
 use comfy_table::presets::UTF8_FULL;
 use comfy_table::Table;
-use std::fs::File;
-use std::io::Write;
-use std::path::Path;
 
-use crate::structs::{Config, Context, Service, Task};
+use crate::edit::edit_context;
+use crate::errors::{report, report_as, Error, TaskError};
+use crate::filter::Filter;
+use crate::history::{print_diff, print_log};
+use crate::journal::{redo, undo, write_data, Store};
+use crate::modify::{apply_changes, print_added, print_summary, Change, NewTask};
+use crate::output::{print_records, record_changes, ContextRecord, Format};
+use crate::structs::{Config, Context, ListOptions, Service, Task};
 use crate::utils::{
-    get_or_create_data_file, get_or_create_data_file_ssh, get_remote_path, get_sftp, parse_args,
-    parse_ids, print_tasks,
+    build_search_regex, find_context, get_context_index, get_or_create_data_file,
+    get_or_create_data_file_ssh, get_sftp, next_context_id, parse_args, print_list, print_search,
+    reorder_tasks, repair_context_ids, select_ids,
 };
 
 #[derive(Debug)]
 pub struct FileService;
 
+impl FileService {
+    /// Replaces the tasks of the context, as edited with `edit`.
+    pub fn set_tasks(&self, config: &Config, tasks: Vec<Task>) {
+        match get_file_data(config) {
+            Ok((mut data, index)) => {
+                let before = data.clone();
+                data[index].tasks = tasks;
+                write_to_file(&before, data, config)
+            }
+            Err(err) => {
+                report(err);
+            }
+        }
+    }
+}
+
 impl Service for FileService {
-    fn edit_context(&self, config: &Config, id: usize, name: String) {
-        match get_file_data(&config) {
-            Ok((data, _)) => {
-                let active_context = data.iter().find(|ctx| ctx.id == id);
-
-                match active_context {
-                    Some(_) => {
-                        let updated_data: Vec<Context> = data
-                            .into_iter()
-                            .map(|mut context| {
-                                if context.id != id {
-                                    return context;
-                                }
-
-                                context.name = name.clone();
-                                return context;
-                            })
-                            .collect();
-
-                        write_to_file(updated_data, &config)
-                    }
-                    None => {
-                        println!("No context found with this ID: {}", id);
+    fn edit_context(&self, config: &Config, name: String, new_name: String) {
+        match get_data(config) {
+            Ok(mut data) => match find_context(&data, &name) {
+                Some(index) => {
+                    if data.iter().any(|ctx| ctx.name == new_name) {
+                        report_as(
+                            TaskError::Conflict,
+                            format!("A context already exists with this name: {}", new_name),
+                        );
+                        return;
                     }
+
+                    let before = data.clone();
+                    data[index].name = new_name;
+                    write_to_file(&before, data, config)
                 }
-            }
+                None => {
+                    report_as(
+                        TaskError::NotFound,
+                        format!("No context found with this name or ID: {}", name),
+                    );
+                }
+            },
             Err(err) => {
-                println!("{}", err);
+                report(err);
             }
         }
     }
 
-    fn edit_task(&self, config: &Config, id: usize, content: String) {
-        match get_file_data(&config) {
+    fn edit_task(&self, config: &Config, args: String, content: String) {
+        match get_file_data(config) {
             Ok((mut data, index)) => {
+                let before = data.clone();
+                let ids = match select_ids(&data[index], &args, None) {
+                    Ok(ids) => ids,
+                    Err(err) => {
+                        report(err);
+                        return;
+                    }
+                };
                 let active_tasks = data[index].tasks.clone();
+                let date = Local::now().to_string();
 
                 data[index].tasks = active_tasks
                     .into_iter()
                     .map(|mut task| {
-                        if id == task.id {
+                        if ids.contains(&task.id) && task.content != content {
                             task.content = content.clone();
+                            task.modification_date = date.clone();
                             return task;
                         }
 
@@ -67,44 +93,49 @@ impl Service for FileService {
                     })
                     .collect();
 
-                write_to_file(data, &config);
+                write_to_file(&before, data, config);
             }
             Err(err) => {
-                println!("{}", err);
+                report(err);
             }
         }
     }
 
     fn use_context(&self, config: &Config, name: String) {
-        match get_file_data(&config) {
-            Ok((mut data, _)) => {
-                let exists = data.iter().find(|ctx| ctx.name == name.to_owned());
-
-                if exists.is_none() {
-                    let new_context = Context::new(&name, data.len());
-                    data.push(new_context);
-                }
+        match get_data(config) {
+            Ok(mut data) => {
+                let before = data.clone();
+                let id = match find_context(&data, &name) {
+                    Some(index) => data[index].id,
+                    None => {
+                        let new_context = Context::new(&name, next_context_id(&data));
+                        let id = new_context.id;
+                        data.push(new_context);
+                        id
+                    }
+                };
 
                 let updated_data = data
                     .into_iter()
                     .map(|mut ctx| {
-                        ctx.active = ctx.name == name.to_owned();
+                        ctx.active = ctx.id == id;
 
                         ctx
                     })
                     .collect();
 
-                write_to_file(updated_data, config)
+                write_to_file(&before, updated_data, config)
             }
             Err(err) => {
-                println!("{}", err);
+                report(err);
             }
         }
     }
 
     fn add_task(&self, config: &Config, task: String) {
-        match get_file_data(&config) {
+        match get_file_data(config) {
             Ok((mut data, index)) => {
+                let before = data.clone();
                 let date = Local::now();
 
                 let task: Task = Task {
@@ -113,23 +144,55 @@ impl Service for FileService {
                     done: false,
                     creation_date: date.to_string(),
                     modification_date: date.to_string(),
+                    priority: data[index].meta.default_priority,
+                    tags: data[index].meta.default_tags.clone(),
+                    due: None,
                 };
 
                 data[index].tasks.push(task);
 
-                write_to_file(data, &config);
+                write_to_file(&before, data, config);
+            }
+            Err(err) => {
+                report(err);
+            }
+        }
+    }
+
+    fn add_tasks(&self, config: &Config, tasks: Vec<NewTask>) {
+        match get_file_data(config) {
+            Ok((mut data, index)) => {
+                let before = data.clone();
+                let count = tasks.len();
+                for new_task in tasks {
+                    let task = new_task.to_task(&data[index], data[index].tasks.len() + 1);
+                    data[index].tasks.push(task);
+                }
+
+                write_to_file(&before, data, config);
+                if !config.dry_run {
+                    print_added(config, count);
+                }
             }
             Err(err) => {
-                println!("{}", err);
+                report(err);
             }
         }
     }
 
-    fn del_task(&self, config: &Config, args: String) {
-        match get_file_data(&config) {
+    fn del_task(&self, config: &Config, args: String, filter: Option<Filter>) {
+        match get_file_data(config) {
             Ok((mut data, index)) => {
-                let ids = parse_ids(parse_args(&args));
+                let before = data.clone();
+                let ids = match select_ids(&data[index], &args, filter.as_ref()) {
+                    Ok(ids) => ids,
+                    Err(err) => {
+                        report(err);
+                        return;
+                    }
+                };
                 let mut counter = 0;
+                let date = Local::now().to_string();
 
                 let active_tasks = data[index].tasks.clone();
 
@@ -141,201 +204,389 @@ impl Service for FileService {
                         }
 
                         counter += 1;
-                        task.id = counter;
+                        // The remaining tasks after a deleted one are renumbered
+                        if task.id != counter {
+                            task.id = counter;
+                            task.modification_date = date.clone();
+                        }
 
                         Some(task)
                     })
                     .collect();
 
-                write_to_file(data, &config);
+                write_to_file(&before, data, config);
             }
             Err(err) => {
-                println!("{}", err);
+                report(err);
             }
         }
     }
 
-    fn list_tasks(&self, config: &Config, all: bool) {
-        match get_file_data(&config) {
+    fn list_tasks(&self, config: &Config, options: ListOptions) {
+        let data_res = if options.all || !options.contexts.is_empty() {
+            get_data(config).map(|data| (data, 0))
+        } else {
+            get_file_data(config)
+        };
+
+        match data_res {
             Ok((data, index)) => {
-                if all {
-                    for ctx in &data {
-                        print_tasks(&config, &ctx);
+                let contexts: Vec<Context> = if !options.contexts.is_empty() {
+                    let mut contexts = vec![];
+                    for name in &options.contexts {
+                        match find_context(&data, name) {
+                            Some(index) => contexts.push(data[index].clone()),
+                            None => {
+                                report_as(
+                                    TaskError::NotFound,
+                                    format!("No context found with this name or ID: {}", name),
+                                );
+                                return;
+                            }
+                        }
                     }
+                    contexts
+                } else if options.all {
+                    data.into_iter().filter(|ctx| !ctx.archived).collect()
                 } else {
-                    print_tasks(&config, &data[index]);
-                }
+                    vec![data[index].clone()]
+                };
+
+                print_list(config, &contexts, &options);
+            }
+            Err(err) => {
+                report(err);
+            }
+        }
+    }
+
+    fn search_tasks(&self, config: &Config, pattern: String, regex: bool) {
+        let search = match build_search_regex(&pattern, regex) {
+            Ok(search) => search,
+            Err(err) => {
+                report_as(TaskError::InvalidInput, err);
+                return;
             }
+        };
+
+        match get_data(config) {
+            Ok(data) => print_search(config, &data, &search),
             Err(err) => {
-                println!("{}", err);
+                report(err);
             }
         }
     }
 
-    fn mark_done(&self, config: &Config, args: String) {
-        match get_file_data(&config) {
+    fn mark_done(&self, config: &Config, args: String, filter: Option<Filter>) {
+        match get_file_data(config) {
             Ok((mut data, index)) => {
-                let ids = parse_ids(parse_args(&args));
+                let before = data.clone();
+                let ids = match select_ids(&data[index], &args, filter.as_ref()) {
+                    Ok(ids) => ids,
+                    Err(err) => {
+                        report(err);
+                        return;
+                    }
+                };
+
+                let date = Local::now().to_string();
 
                 data[index].tasks = data[index]
                     .tasks
                     .iter()
                     .map(|task| {
                         let mut cloned = task.clone();
-                        if ids.contains(&cloned.id) {
-                            cloned.done = true
+                        if ids.contains(&cloned.id) && !cloned.done {
+                            cloned.done = true;
+                            cloned.modification_date = date.clone();
                         }
 
-                        cloned.to_owned()
+                        cloned
                     })
                     .collect();
 
-                write_to_file(data, &config);
+                write_to_file(&before, data, config);
             }
             Err(err) => {
-                println!("{}", err);
+                report(err);
+            }
+        }
+    }
+
+    fn modify_tasks(
+        &self,
+        config: &Config,
+        args: String,
+        changes: Vec<Change>,
+        filter: Option<Filter>,
+    ) {
+        match get_file_data(config) {
+            Ok((mut data, index)) => {
+                let before = data.clone();
+                let ids = match select_ids(&data[index], &args, filter.as_ref()) {
+                    Ok(ids) => ids,
+                    Err(err) => {
+                        report(err);
+                        return;
+                    }
+                };
+
+                let summary = apply_changes(&mut data[index].tasks, &ids, &changes);
+                if !summary.is_empty() {
+                    write_to_file(&before, data, config);
+                }
+
+                if !config.dry_run {
+                    print_summary(config, &summary);
+                }
+            }
+            Err(err) => {
+                report(err);
+            }
+        }
+    }
+
+    fn move_task(&self, config: &Config, id: usize, position: usize) {
+        match get_file_data(config) {
+            Ok((mut data, index)) => {
+                let before = data.clone();
+                match reorder_tasks(&mut data[index].tasks, id, position) {
+                    Ok(()) => write_to_file(&before, data, config),
+                    Err(err) => report(err),
+                }
+            }
+            Err(err) => {
+                report(err);
             }
         }
     }
 
     fn clear_tasks(&self, config: &Config) {
-        match get_file_data(&config) {
+        match get_file_data(config) {
             Ok((mut data, index)) => {
+                let before = data.clone();
                 data[index].tasks = vec![];
-                write_to_file(data, &config)
+                write_to_file(&before, data, config)
             }
             Err(err) => {
-                println!("{}", err);
+                report(err);
             }
         }
     }
 
     fn del_context(&self, config: &Config, args: String) {
-        match get_file_data(&config) {
-            Ok((data, _)) => {
+        match get_data(config) {
+            Ok(data) => {
+                let before = data.clone();
                 let ctx_names_or_ids = parse_args(&args);
 
+                let ids: Vec<usize> = ctx_names_or_ids
+                    .iter()
+                    .filter_map(|name_or_id| find_context(&data, name_or_id.trim()))
+                    .map(|index| data[index].id)
+                    .collect();
+
                 let mut updated_data: Vec<Context> = data
                     .into_iter()
-                    .enumerate()
-                    .filter(|(index, ctx)| {
-                        let id = (index + 1).to_string();
-                        if ctx_names_or_ids.contains(&ctx.name.as_str())
-                            || ctx_names_or_ids.contains(&id.as_str())
-                        {
-                            return false;
-                        }
-
-                        true
-                    })
-                    .map(|(_, ctx)| ctx)
+                    .filter(|ctx| !ids.contains(&ctx.id))
                     .collect();
 
                 let active_ctx = updated_data.iter().find(|ctx| ctx.active);
 
-                if active_ctx.is_none() && !updated_data.get(0).is_none() {
+                if active_ctx.is_none() && !updated_data.is_empty() {
                     updated_data[0].active = true;
                 }
 
-                write_to_file(updated_data, &config);
+                write_to_file(&before, updated_data, config);
+            }
+            Err(err) => {
+                report(err);
+            }
+        }
+    }
+
+    fn archive_context(&self, config: &Config, name: String, archived: bool) {
+        match get_data(config) {
+            Ok(mut data) => match find_context(&data, &name) {
+                Some(index) => {
+                    let before = data.clone();
+                    data[index].archived = archived;
+                    write_to_file(&before, data, config);
+                }
+                None => {
+                    report_as(
+                        TaskError::NotFound,
+                        format!("No context found with this name or ID: {}", name),
+                    );
+                }
+            },
+            Err(err) => {
+                report(err);
             }
+        }
+    }
+
+    fn set_context_meta(&self, config: &Config, name: String, key: String, value: String) {
+        match get_data(config) {
+            Ok(mut data) => match find_context(&data, &name) {
+                Some(index) => {
+                    let before = data.clone();
+                    match data[index].meta.set(&key, &value) {
+                        Ok(()) => write_to_file(&before, data, config),
+                        Err(err) => report_as(TaskError::InvalidInput, err),
+                    }
+                }
+                None => {
+                    report_as(
+                        TaskError::NotFound,
+                        format!("No context found with this name or ID: {}", name),
+                    );
+                }
+            },
             Err(err) => {
-                println!("{}", err);
+                report(err);
             }
         }
     }
 
-    fn list_contexts(&self, config: &Config) {
-        match get_file_data(&config) {
-            Ok((data, _)) => {
+    fn list_contexts(&self, config: &Config, all: bool) {
+        match get_data(config) {
+            Ok(data) if config.format != Format::Table => {
+                let records: Vec<ContextRecord> = data
+                    .iter()
+                    .filter(|ctx| all || !ctx.archived)
+                    .map(ContextRecord::from)
+                    .collect();
+                print_records(config.format, &records);
+            }
+            Ok(data) => {
                 let mut table = Table::new();
                 table
                     .load_preset(UTF8_FULL)
                     .apply_modifier(UTF8_ROUND_CORNERS);
 
-                for (i, ctx) in data.iter().enumerate() {
-                    let active = if ctx.active { "active" } else { "" };
+                for ctx in &data {
+                    if ctx.archived && !all {
+                        continue;
+                    }
+
+                    let active = if ctx.active {
+                        "active"
+                    } else if ctx.archived {
+                        "archived"
+                    } else {
+                        ""
+                    };
                     table.add_row(vec![
-                        (i + 1).to_string(),
+                        ctx.id.to_string(),
                         ctx.name.to_owned(),
+                        ctx.meta.description.to_owned(),
                         format!("{} tasks", ctx.tasks.len()),
                         active.to_string(),
                     ]);
                 }
 
-                if data.len() == 0 {
+                if data.is_empty() {
                     table.add_row(vec!["Add your first context using: tasks use {{context}}"]);
                 }
 
                 println!("{table}");
             }
             Err(err) => {
-                println!("{}", err);
+                report(err);
             }
         }
     }
-}
 
-fn get_file_data(config: &Config) -> Result<(Vec<Context>, usize), String> {
-    let data_res = if config.ssh_ip.is_empty() {
-        get_or_create_data_file(&config.local_file_path, &config.folder_path, true)
-    } else {
-        get_or_create_data_file_ssh(&config, true)
-    };
-
-    if data_res.is_err() {
-        return Err(data_res.unwrap_err());
+    fn edit_tasks(&self, config: &Config, context: Option<String>) {
+        edit_context(config, self, context);
     }
 
-    let data = data_res.unwrap();
-    let active_index = data.iter().position(|context| context.active == true);
+    fn get_contexts(&self, config: &Config) -> Result<Vec<Context>, Error> {
+        get_data(config)
+    }
 
-    let cli = Cli::parse();
-    match cli.command {
-        Some(Commands::Use(_)) => {
-            let ctx_index = active_index.unwrap();
-            Ok((data, ctx_index))
+    fn read_contexts(&self, config: &Config) -> Result<Vec<Context>, Error> {
+        if config.ssh_ip.is_empty() {
+            get_or_create_data_file(&config.local_file_path, &config.folder_path, false)
+        } else {
+            get_or_create_data_file_ssh(config, false)
         }
-        _ => {
-            if active_index.is_none() {
-                return Err(format!(
-                    "No current active context, let's create one using task use {{name}}"
-                ));
-            }
+    }
 
-            let ctx_index = active_index.unwrap_or(0);
-            Ok((data, ctx_index))
-        }
+    fn find_contexts(&self, config: &Config, names: &str) -> Result<Vec<ContextRecord>, Error> {
+        let data = get_data(config)?;
+
+        Ok(parse_args(names)
+            .iter()
+            .filter_map(|name_or_id| find_context(&data, name_or_id.trim()))
+            .map(|index| ContextRecord::from(&data[index]))
+            .collect())
     }
-}
 
-fn write_to_file(data: Vec<Context>, config: &Config) {
-    let json = serde_json::to_string(&data).expect("Error when stringifying data");
+    fn show_log(&self, config: &Config, since: Option<NaiveDateTime>) {
+        let context = config.context.as_deref();
+        with_store(config, |store| print_log(store, since, context));
+    }
 
-    if config.ssh_ip.is_empty() {
-        let mut file = File::create(&config.local_file_path).expect("Error when creating file");
+    fn undo(&self, config: &Config, steps: usize) {
+        with_store(config, |store| undo(store, steps));
+    }
 
-        file.write(json.as_bytes())
-            .expect("Error when writing to file");
+    fn redo(&self, config: &Config, steps: usize) {
+        with_store(config, |store| redo(store, steps));
+    }
+}
 
-        return;
+fn get_data(config: &Config) -> Result<Vec<Context>, Error> {
+    let mut data = if config.ssh_ip.is_empty() {
+        get_or_create_data_file(&config.local_file_path, &config.folder_path, true)?
+    } else {
+        get_or_create_data_file_ssh(config, true)?
+    };
+
+    // Save the new ids right away so the warning is only printed once
+    let before = data.clone();
+    if repair_context_ids(&mut data) && !config.dry_run {
+        write_to_file(&before, data.clone(), config);
     }
 
-    let sftp_res = get_sftp(&config);
-    if sftp_res.is_err() {
+    Ok(data)
+}
+
+fn get_file_data(config: &Config) -> Result<(Vec<Context>, usize), Error> {
+    let data = get_data(config)?;
+    let ctx_index = get_context_index(&data, config)?;
+
+    Ok((data, ctx_index))
+}
+
+/// Saves the data, `before` being the data it was loaded as, to journal and diff the change.
+fn write_to_file(before: &[Context], data: Vec<Context>, config: &Config) {
+    if config.dry_run {
+        print_diff(before, &data);
         return;
     }
 
-    let sftp = sftp_res.unwrap();
-    let path_str = get_remote_path(&config);
-    let path = Path::new(&path_str);
-
-    let mut file = sftp
-        .create(path)
-        .expect("Impossible to write on remote file");
+    let json = serde_json::to_string(&data).expect("Error when stringifying data");
+    let before_json = serde_json::to_string(before).expect("Error when stringifying data");
+    with_store(config, |store| {
+        match write_data(store, &before_json, &json) {
+            Ok(()) => record_changes(config, before, &data),
+            Err(err) => report(err),
+        }
+    });
+}
 
-    file.write(json.as_bytes())
-        .expect("Error when writing to file");
+/// Runs the action on the local or remote storage of the data file.
+fn with_store(config: &Config, action: impl FnOnce(&Store)) {
+    if config.ssh_ip.is_empty() {
+        action(&Store::Local(config));
+        return;
+    }
 
-    file.close().unwrap();
+    match get_sftp(config) {
+        Ok(sftp) => action(&Store::Remote(config, &sftp)),
+        Err(err) => report(err),
+    }
 }
@@ -1,7 +1,18 @@
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::env;
+use std::fmt;
+use std::str::FromStr;
 use terminal_size::{terminal_size, Height, Width};
 
+use crate::args::Cli;
+use crate::errors::Error;
+use crate::filter::Filter;
+use crate::modify::{Change, NewTask};
+use crate::output::{ContextRecord, Format};
+use crate::utils::{parse_args, parse_color};
+
 const DEFAULT_LINE_LENGTH: usize = 50;
 const LAYOUT: usize = 15;
 const LINE_LEN_FALLBACK: usize = 10;
@@ -13,6 +24,28 @@ pub struct Task {
     pub done: bool,
     pub creation_date: String,
     pub modification_date: String,
+    #[serde(default)]
+    pub priority: Option<Priority>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub due: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    L,
+    M,
+    H,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ContextMeta {
+    pub description: String,
+    pub color: String,
+    pub default_priority: Option<Priority>,
+    pub default_tags: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -20,6 +53,10 @@ pub struct Context {
     pub id: usize,
     pub name: String,
     pub active: bool,
+    #[serde(default)]
+    pub archived: bool,
+    #[serde(flatten)]
+    pub meta: ContextMeta,
     pub tasks: Vec<Task>,
 }
 
@@ -29,6 +66,10 @@ pub struct ContextCountTask {
     pub name: String,
     pub task_count: u64,
     pub active: bool,
+    #[serde(default)]
+    pub archived: bool,
+    #[serde(default)]
+    pub description: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -36,6 +77,8 @@ pub struct ContextOnly {
     pub id: usize,
     pub name: String,
     pub active: bool,
+    #[serde(default)]
+    pub archived: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -43,6 +86,8 @@ pub struct ContextRequest {
     pub name: String,
     pub active: bool,
     pub simple_create: bool,
+    #[serde(flatten)]
+    pub meta: ContextMeta,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -55,30 +100,298 @@ pub struct TaskRequest {
     pub content: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct TaskDoneRequest {
-    pub done: bool,
-}
-
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TaskRequestFull {
     pub content: String,
+    pub done: bool,
     pub context_id: i32,
     pub creation_date: String,
     pub modification_date: String,
+    pub priority: Option<Priority>,
+    pub tags: Vec<String>,
+    pub due: Option<String>,
 }
 
 impl Context {
-    pub fn new(name: &String, size: usize) -> Self {
+    pub fn new(name: &str, id: usize) -> Self {
         Self {
-            id: size + 1,
+            id,
             name: name.to_owned(),
             tasks: vec![],
             active: true,
+            archived: false,
+            meta: ContextMeta::default(),
+        }
+    }
+}
+
+impl ContextMeta {
+    /// Updates one metadata field from its `setc` key, an empty value resets it.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "description" => self.description = value.to_owned(),
+            "color" => {
+                if !value.is_empty() && parse_color(value).is_none() {
+                    return Err(format!("Unknown color: {value}"));
+                }
+                self.color = value.to_owned();
+            }
+            "priority" => {
+                self.default_priority = if value.is_empty() {
+                    None
+                } else {
+                    Some(value.parse()?)
+                };
+            }
+            "tags" => {
+                self.default_tags = parse_args(value)
+                    .into_iter()
+                    .map(|tag| tag.trim().trim_start_matches('+').to_owned())
+                    .filter(|tag| !tag.is_empty())
+                    .collect();
+            }
+            _ => {
+                return Err(format!(
+                    "Unknown context key: {key}, use description, color, priority or tags"
+                ))
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Priority {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "l" | "low" => Ok(Priority::L),
+            "m" | "medium" => Ok(Priority::M),
+            "h" | "high" => Ok(Priority::H),
+            _ => Err(format!("Invalid priority: {value}, use H, M or L")),
+        }
+    }
+}
+
+impl FromStr for Sort {
+    type Err = String;
+
+    /// Parses `key` or `key:asc|desc`, `id` (or `manual`) being the manual order.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (key, direction) = value.split_once(':').unwrap_or((value, "asc"));
+
+        let key = match key.to_lowercase().as_str() {
+            "id" | "manual" => SortKey::Id,
+            "created" => SortKey::Created,
+            "modified" => SortKey::Modified,
+            "status" => SortKey::Status,
+            "content" => SortKey::Content,
+            "due" => SortKey::Due,
+            "priority" => SortKey::Priority,
+            _ => {
+                return Err(format!(
+                    "Invalid sort key: {key}, use id, created, modified, status, content, due or priority"
+                ))
+            }
+        };
+
+        let desc = match direction.to_lowercase().as_str() {
+            "asc" => false,
+            "desc" => true,
+            _ => {
+                return Err(format!(
+                    "Invalid sort direction: {direction}, use asc or desc"
+                ))
+            }
+        };
+
+        Ok(Sort { key, desc })
+    }
+}
+
+impl FromStr for GroupBy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "status" => Ok(GroupBy::Status),
+            "context" => Ok(GroupBy::Context),
+            "tag" => Ok(GroupBy::Tag),
+            _ => Err(format!(
+                "Invalid group: {value}, use status, context or tag"
+            )),
+        }
+    }
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "id" => Ok(Column::Id),
+            "status" => Ok(Column::Status),
+            "content" => Ok(Column::Content),
+            "priority" => Ok(Column::Priority),
+            "tags" => Ok(Column::Tags),
+            "due" => Ok(Column::Due),
+            "created" => Ok(Column::Created),
+            "modified" => Ok(Column::Modified),
+            "context" => Ok(Column::Context),
+            _ => Err(format!(
+                "Invalid column: {value}, use id, status, content, priority, tags, due, created, modified or context"
+            )),
         }
     }
 }
 
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let priority = match self {
+            Priority::L => "L",
+            Priority::M => "M",
+            Priority::H => "H",
+        };
+
+        write!(f, "{priority}")
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ListOptions {
+    pub all: bool,
+    pub filter: Option<Filter>,
+    pub sort: Option<Sort>,
+    pub group_by: Option<GroupBy>,
+    pub columns: Vec<Column>,
+    pub contexts: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Column {
+    Id,
+    Status,
+    Content,
+    Priority,
+    Tags,
+    Due,
+    Created,
+    Modified,
+    Context,
+}
+
+/// Named listing defined in the config and run with `task report {name}` or `task {name}`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Report {
+    pub description: String,
+    pub filter: String,
+    pub sort: String,
+    pub group_by: String,
+    pub columns: Vec<String>,
+    /// Contexts to list (names or ids), the active one when empty
+    pub contexts: Vec<String>,
+    /// Lists every context that is not archived
+    pub all: bool,
+}
+
+/// Mutation recorded in the journal stored next to the data file, with the data
+/// before it in the undo stack and after it in the redo one.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JournalEntry {
+    pub command: String,
+    pub date: String,
+    pub data: String,
+}
+
+/// The other side of each entry is the data of the entry above it in its stack,
+/// or `head` for the top one, so each version of the data is stored once.
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct Journal {
+    pub undo: Vec<JournalEntry>,
+    pub redo: Vec<JournalEntry>,
+    /// The data as last written by task
+    pub head: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Created,
+    Edited,
+    Completed,
+    Reopened,
+    Deleted,
+    ContextCreated,
+    ContextRenamed,
+    ContextDeleted,
+    ContextSwitched,
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            EventKind::Created => "created",
+            EventKind::Edited => "edited",
+            EventKind::Completed => "completed",
+            EventKind::Reopened => "reopened",
+            EventKind::Deleted => "deleted",
+            EventKind::ContextCreated => "context created",
+            EventKind::ContextRenamed => "context renamed",
+            EventKind::ContextDeleted => "context deleted",
+            EventKind::ContextSwitched => "context switched",
+        };
+        write!(f, "{kind}")
+    }
+}
+
+/// Line of the activity log stored next to the data file.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Event {
+    pub date: String,
+    pub kind: EventKind,
+    pub context_id: usize,
+    pub context: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+}
+
+/// Options of a `task-{name}` plugin found on the PATH.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Plugin {
+    /// Sends the data as JSON on the plugin stdin
+    pub stdin: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKey {
+    Id,
+    Created,
+    Modified,
+    Status,
+    Content,
+    Due,
+    Priority,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Sort {
+    pub key: SortKey,
+    pub desc: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupBy {
+    Status,
+    Context,
+    Tag,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(default)]
 pub struct UserConfig {
@@ -88,9 +401,15 @@ pub struct UserConfig {
     pub local_file_path: String,
     pub api_url: String,
     pub api_key: String,
+    pub sort: String,
+    pub group_by: String,
+    pub reports: HashMap<String, Report>,
+    pub aliases: HashMap<String, String>,
+    pub plugins: HashMap<String, Plugin>,
+    pub watch_interval: u64,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Config {
     pub ssh_ip: String,
     pub ssh_username: String,
@@ -100,10 +419,22 @@ pub struct Config {
     pub api_url: String,
     pub api_key: String,
     pub folder_path: String,
+    pub context: Option<String>,
+    pub sort: String,
+    pub group_by: String,
+    pub reports: HashMap<String, Report>,
+    pub plugins: HashMap<String, Plugin>,
+    pub yes: bool,
+    pub dry_run: bool,
+    pub quiet: bool,
+    pub watch_interval: u64,
+    pub format: Format,
+    /// The command line being run, recorded in the journal
+    pub command: String,
 }
 
 impl Config {
-    pub fn new(config: UserConfig) -> Self {
+    pub fn new(config: UserConfig, cli: &Cli, command: String) -> Self {
         let [file_path, folder_path] = get_file_paths(&config);
         Self {
             ssh_ip: config.ssh_ip,
@@ -114,6 +445,30 @@ impl Config {
             api_url: config.api_url,
             api_key: config.api_key,
             folder_path,
+            context: cli.context.clone(),
+            sort: config.sort,
+            group_by: config.group_by,
+            reports: config.reports,
+            plugins: config.plugins,
+            yes: cli.yes,
+            dry_run: cli.dry_run,
+            quiet: cli.quiet,
+            watch_interval: config.watch_interval,
+            format: cli.format,
+            command,
+        }
+    }
+
+    /// The config of a command returned by a plugin, its global options added to
+    /// the ones the plugin was run with, the output format staying the same. Only the
+    /// user can skip the confirmations, `--yes` is ignored.
+    pub fn with_options(&self, cli: &Cli, command: String) -> Self {
+        Self {
+            command,
+            context: cli.context.clone().or_else(|| self.context.clone()),
+            dry_run: self.dry_run || cli.dry_run,
+            quiet: self.quiet || cli.quiet,
+            ..self.clone()
         }
     }
 }
@@ -127,27 +482,53 @@ impl ::std::default::Default for UserConfig {
             local_file_path: "".into(),
             api_url: "".into(),
             api_key: "".into(),
+            sort: "".into(),
+            group_by: "".into(),
+            reports: HashMap::new(),
+            aliases: HashMap::new(),
+            plugins: HashMap::new(),
+            watch_interval: 5,
         }
     }
 }
 
 pub trait Service {
-    fn list_tasks(&self, config: &Config, all: bool);
-    fn list_contexts(&self, config: &Config);
+    fn list_tasks(&self, config: &Config, options: ListOptions);
+    fn list_contexts(&self, config: &Config, all: bool);
+    fn search_tasks(&self, config: &Config, pattern: String, regex: bool);
     fn add_task(&self, config: &Config, name: String);
-    fn del_task(&self, config: &Config, name: String);
+    fn add_tasks(&self, config: &Config, tasks: Vec<NewTask>);
+    fn del_task(&self, config: &Config, name: String, filter: Option<Filter>);
     fn del_context(&self, config: &Config, name: String);
-    fn edit_task(&self, config: &Config, id: usize, name: String);
-    fn edit_context(&self, config: &Config, id: usize, name: String);
+    fn edit_task(&self, config: &Config, ids: String, name: String);
+    fn edit_context(&self, config: &Config, name: String, new_name: String);
     fn use_context(&self, config: &Config, name: String);
-    fn mark_done(&self, config: &Config, name: String);
+    fn archive_context(&self, config: &Config, name: String, archived: bool);
+    fn set_context_meta(&self, config: &Config, name: String, key: String, value: String);
+    fn mark_done(&self, config: &Config, name: String, filter: Option<Filter>);
+    fn modify_tasks(
+        &self,
+        config: &Config,
+        name: String,
+        changes: Vec<Change>,
+        filter: Option<Filter>,
+    );
+    fn move_task(&self, config: &Config, id: usize, position: usize);
     fn clear_tasks(&self, config: &Config);
+    fn edit_tasks(&self, config: &Config, context: Option<String>);
+    fn get_contexts(&self, config: &Config) -> Result<Vec<Context>, Error>;
+    /// The data as stored, without creating or repairing the data file.
+    fn read_contexts(&self, config: &Config) -> Result<Vec<Context>, Error>;
+    fn find_contexts(&self, config: &Config, names: &str) -> Result<Vec<ContextRecord>, Error>;
+    fn undo(&self, config: &Config, steps: usize);
+    fn show_log(&self, config: &Config, since: Option<NaiveDateTime>);
+    fn redo(&self, config: &Config, steps: usize);
 }
 
-pub fn normalize_path(path: &String, starts_with_backslash: bool) -> String {
-    if starts_with_backslash && !path.starts_with("/") {
+pub fn normalize_path(path: &str, starts_with_backslash: bool) -> String {
+    if starts_with_backslash && !path.starts_with('/') {
         format!("/{path}")
-    } else if !starts_with_backslash && path.starts_with("/") {
+    } else if !starts_with_backslash && path.starts_with('/') {
         let mut chars = path.chars();
         chars.next();
         chars.collect::<String>()
@@ -173,7 +554,7 @@ pub fn get_file_paths(config: &UserConfig) -> [String; 2] {
     [file_path, folder_path]
 }
 
-fn get_terminal_width() -> usize {
+pub fn get_terminal_width() -> usize {
     let size = terminal_size();
     if let Some((Width(w), Height(_))) = size {
         let width = usize::from(w);
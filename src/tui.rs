@@ -0,0 +1,416 @@
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::io;
+
+use crate::errors::{report, report_as, Error, TaskError};
+use crate::modify::Change;
+use crate::structs::{Config, Context, Service};
+use crate::utils::{capture_errors, capture_output, format_task};
+
+const HELP: &str =
+    "a add  e edit  d delete  space done  / search  h/l context  u use  r reload  q quit";
+
+#[derive(Debug, PartialEq)]
+enum Mode {
+    Normal,
+    Search,
+    Add,
+    Edit,
+    Delete,
+}
+
+struct App<'a, S: Service> {
+    config: &'a Config,
+    service: &'a S,
+    contexts: Vec<Context>,
+    context: usize,
+    tasks: ListState,
+    mode: Mode,
+    input: String,
+    search: String,
+    message: String,
+}
+
+/// Full-screen interface listing the contexts and their tasks,
+/// every change going through the service like the other commands.
+pub fn run_tui(config: &Config, service: &impl Service) {
+    let mut app = App {
+        config,
+        service,
+        contexts: vec![],
+        context: 0,
+        tasks: ListState::default(),
+        mode: Mode::Normal,
+        input: String::new(),
+        search: String::new(),
+        message: String::new(),
+    };
+
+    if let Err(err) = app.reload() {
+        report(err);
+        return;
+    }
+    if let Some(active) = app.contexts.iter().position(|ctx| ctx.active) {
+        app.context = active;
+    }
+    app.select_first();
+
+    let mut terminal = ratatui::init();
+    let res = app.run(&mut terminal);
+    ratatui::restore();
+
+    if let Err(err) = res {
+        report_as(TaskError::Storage, err);
+    }
+}
+
+impl<S: Service> App<'_, S> {
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            let quit = match self.mode {
+                Mode::Normal => self.on_normal_key(key),
+                _ => {
+                    self.on_input_key(key);
+                    false
+                }
+            };
+            if quit {
+                return Ok(());
+            }
+        }
+    }
+
+    fn on_normal_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return true,
+            KeyCode::Char('j') | KeyCode::Down => self.tasks.select_next(),
+            KeyCode::Char('k') | KeyCode::Up => self.tasks.select_previous(),
+            KeyCode::Char('g') | KeyCode::Home => self.tasks.select_first(),
+            KeyCode::Char('G') | KeyCode::End => self.tasks.select_last(),
+            KeyCode::Char('h') | KeyCode::Left | KeyCode::BackTab => self.switch_context(-1),
+            KeyCode::Char('l') | KeyCode::Right | KeyCode::Tab => self.switch_context(1),
+            KeyCode::Char(' ') | KeyCode::Char('x') => self.toggle_done(),
+            KeyCode::Char('u') | KeyCode::Enter => self.use_context(),
+            KeyCode::Char('r') => self.refresh(String::from("Reloaded")),
+            KeyCode::Char('/') => {
+                self.mode = Mode::Search;
+                self.input = self.search.clone();
+            }
+            KeyCode::Char('a') => {
+                self.mode = Mode::Add;
+                self.input.clear();
+            }
+            KeyCode::Char('e') => {
+                if let Some((ctx, task)) = self.selected() {
+                    self.input = self.contexts[ctx].tasks[task].content.clone();
+                    self.mode = Mode::Edit;
+                }
+            }
+            KeyCode::Char('d') if self.selected().is_some() => self.mode = Mode::Delete,
+            _ => {}
+        }
+
+        false
+    }
+
+    fn on_input_key(&mut self, key: KeyEvent) {
+        if self.mode == Mode::Delete {
+            if let KeyCode::Char('y') = key.code {
+                self.delete_task();
+            }
+            self.mode = Mode::Normal;
+            return;
+        }
+
+        match key.code {
+            KeyCode::Esc => {
+                if self.mode == Mode::Search {
+                    self.search.clear();
+                    self.select_first();
+                }
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Enter => {
+                match self.mode {
+                    Mode::Add => self.add_task(),
+                    Mode::Edit => self.edit_task(),
+                    _ => {}
+                }
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+                self.on_input_change();
+            }
+            KeyCode::Char(c) => {
+                self.input.push(c);
+                self.on_input_change();
+            }
+            _ => {}
+        }
+    }
+
+    /// The search is applied while typing.
+    fn on_input_change(&mut self) {
+        if self.mode == Mode::Search {
+            self.search = self.input.clone();
+            self.select_first();
+        }
+    }
+
+    /// Rows of the task list as (context index, task index), the matching tasks
+    /// of every context while searching.
+    fn rows(&self) -> Vec<(usize, usize)> {
+        let search = self.search.to_lowercase();
+
+        self.contexts
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !search.is_empty() || *index == self.context)
+            .flat_map(|(index, ctx)| {
+                ctx.tasks
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, task)| task.content.to_lowercase().contains(&search))
+                    .map(move |(task, _)| (index, task))
+            })
+            .collect()
+    }
+
+    fn selected(&self) -> Option<(usize, usize)> {
+        self.tasks
+            .selected()
+            .and_then(|index| self.rows().get(index).copied())
+    }
+
+    fn select_first(&mut self) {
+        let selected = (!self.rows().is_empty()).then_some(0);
+        self.tasks.select(selected);
+    }
+
+    fn switch_context(&mut self, offset: isize) {
+        if self.contexts.is_empty() {
+            return;
+        }
+
+        let count = self.contexts.len() as isize;
+        self.context = (self.context as isize + offset).rem_euclid(count) as usize;
+        self.select_first();
+    }
+
+    /// Config targeting the given context, tasks ids being their position in it.
+    /// The active one is left implicit, as the API can't target the others.
+    fn context_config(&self, ctx: usize) -> Config {
+        let mut config = self.config.clone();
+        let ctx = &self.contexts[ctx];
+        config.context = (!ctx.active).then(|| ctx.name.clone());
+        config
+    }
+
+    fn toggle_done(&mut self) {
+        let Some((ctx, task)) = self.selected() else {
+            return;
+        };
+
+        let config = self.context_config(ctx);
+        let done = self.contexts[ctx].tasks[task].done;
+        let message = capture(|| {
+            self.service.modify_tasks(
+                &config,
+                (task + 1).to_string(),
+                vec![Change::Done(!done)],
+                None,
+            )
+        });
+        self.refresh(message);
+    }
+
+    fn add_task(&mut self) {
+        let content = self.input.trim().to_owned();
+        if content.is_empty() || self.contexts.is_empty() {
+            return;
+        }
+
+        let config = self.context_config(self.context);
+        let message = capture(|| self.service.add_task(&config, content));
+        self.refresh(message.or_else(|| Some(String::from("Task added"))));
+
+        let last = self.rows().len().checked_sub(1);
+        if self.search.is_empty() {
+            self.tasks.select(last);
+        }
+    }
+
+    fn edit_task(&mut self) {
+        let content = self.input.trim().to_owned();
+        let Some((ctx, task)) = self.selected() else {
+            return;
+        };
+        if content.is_empty() {
+            return;
+        }
+
+        let config = self.context_config(ctx);
+        let message = capture(|| {
+            self.service
+                .edit_task(&config, (task + 1).to_string(), content)
+        });
+        self.refresh(message.or_else(|| Some(String::from("Task updated"))));
+    }
+
+    fn delete_task(&mut self) {
+        let Some((ctx, task)) = self.selected() else {
+            return;
+        };
+
+        let config = self.context_config(ctx);
+        let message = capture(|| self.service.del_task(&config, (task + 1).to_string(), None));
+        self.refresh(message.or_else(|| Some(String::from("Task deleted"))));
+    }
+
+    fn use_context(&mut self) {
+        let Some(ctx) = self.contexts.get(self.context) else {
+            return;
+        };
+
+        let name = ctx.name.clone();
+        let message = capture(|| self.service.use_context(self.config, name));
+        self.refresh(message.or_else(|| Some(String::from("Context switched"))));
+    }
+
+    fn refresh(&mut self, message: impl Into<Option<String>>) {
+        if let Some(message) = message.into() {
+            self.message = message;
+        }
+
+        if let Err(err) = self.reload() {
+            self.message = err.message;
+        }
+
+        let count = self.rows().len();
+        match self.tasks.selected() {
+            _ if count == 0 => self.tasks.select(None),
+            Some(index) if index >= count => self.tasks.select(Some(count - 1)),
+            None => self.tasks.select(Some(0)),
+            _ => {}
+        }
+    }
+
+    /// Fetches the data again, keeping the selected context.
+    fn reload(&mut self) -> Result<(), Error> {
+        let selected = self.contexts.get(self.context).map(|ctx| ctx.name.clone());
+
+        self.contexts = self
+            .service
+            .get_contexts(self.config)?
+            .into_iter()
+            .filter(|ctx| !ctx.archived)
+            .collect();
+
+        self.context = selected
+            .and_then(|name| self.contexts.iter().position(|ctx| ctx.name == name))
+            .unwrap_or(0);
+
+        Ok(())
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, input, status] = Layout::vertical([
+            Constraint::Min(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [contexts_area, tasks_area] =
+            Layout::horizontal([Constraint::Percentage(25), Constraint::Percentage(75)])
+                .areas(main);
+
+        let contexts: Vec<ListItem> = self
+            .contexts
+            .iter()
+            .map(|ctx| {
+                let marker = if ctx.active { "* " } else { "  " };
+                ListItem::new(format!("{marker}{} ({})", ctx.name, ctx.tasks.len()))
+            })
+            .collect();
+        let mut contexts_state =
+            ListState::default().with_selected((!self.contexts.is_empty()).then_some(self.context));
+        frame.render_stateful_widget(
+            List::new(contexts)
+                .block(Block::default().borders(Borders::ALL).title("Contexts"))
+                .highlight_style(Style::default().add_modifier(Modifier::BOLD)),
+            contexts_area,
+            &mut contexts_state,
+        );
+
+        let searching = !self.search.is_empty();
+        let tasks: Vec<ListItem> = self
+            .rows()
+            .into_iter()
+            .map(|(ctx, task)| {
+                let context = &self.contexts[ctx];
+                let task = &context.tasks[task];
+                let status = if task.done { "[X]" } else { "[ ]" };
+                let prefix = if searching {
+                    format!("{}: ", context.name)
+                } else {
+                    String::new()
+                };
+
+                let item = ListItem::new(format!("{status} {prefix}{}", format_task(task)));
+                if task.done {
+                    item.style(Style::default().add_modifier(Modifier::DIM))
+                } else {
+                    item
+                }
+            })
+            .collect();
+        let title = match (searching, self.contexts.get(self.context)) {
+            (true, _) => format!("Search: {}", self.search),
+            (false, Some(ctx)) if !ctx.meta.description.is_empty() => {
+                format!("{} - {}", ctx.name, ctx.meta.description)
+            }
+            (false, Some(ctx)) => ctx.name.clone(),
+            (false, None) => String::from("No context, create one using task use {name}"),
+        };
+        frame.render_stateful_widget(
+            List::new(tasks)
+                .block(Block::default().borders(Borders::ALL).title(title))
+                .highlight_style(Style::default().add_modifier(Modifier::REVERSED)),
+            tasks_area,
+            &mut self.tasks,
+        );
+
+        let prompt = match self.mode {
+            Mode::Normal => Line::from(HELP),
+            Mode::Search => Line::from(format!("/{}", self.input)),
+            Mode::Add => Line::from(format!("Add: {}", self.input)),
+            Mode::Edit => Line::from(format!("Edit: {}", self.input)),
+            Mode::Delete => Line::from("Delete this task? (y/n)"),
+        };
+        frame.render_widget(Paragraph::new(prompt), input);
+        frame.render_widget(Paragraph::new(self.message.as_str()), status);
+    }
+}
+
+/// Runs a service call, returning its first error or the first line it printed
+/// instead of writing over the screen.
+fn capture(action: impl FnOnce()) -> Option<String> {
+    let mut output = vec![];
+    let errors = capture_errors(|| output = capture_output(action));
+
+    errors.into_iter().chain(output).next()
+}
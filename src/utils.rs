@@ -1,49 +1,604 @@
+use chrono::{Datelike, Days, Local, NaiveDate, Weekday};
 use comfy_table::modifiers::UTF8_ROUND_CORNERS;
 use comfy_table::presets::UTF8_FULL;
-use comfy_table::{Cell, Table};
+use comfy_table::{Cell, Color, Table};
+use gag::BufferRedirect;
+use regex::{Regex, RegexBuilder};
 use ssh2::{Session, Sftp};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt::Display;
 use std::fs::{create_dir_all, File};
-use std::io::{BufReader, Write};
+use std::io::{BufReader, IsTerminal, Read, Write};
 use std::net::TcpStream;
 use std::path::Path;
 
-use crate::structs::{Config, Context};
+use crate::errors::Error;
+use crate::filter::Filter;
+use crate::output::{positioned, print_search_records, print_task_records, Format};
+use crate::structs::{Column, Config, Context, GroupBy, ListOptions, Sort, SortKey, Task};
 
-pub fn parse_args(args: &String) -> Vec<&str> {
-    args.split(",").collect()
+pub fn parse_args(args: &str) -> Vec<&str> {
+    args.split(',').collect()
 }
 
-pub fn parse_ids(ids: Vec<&str>) -> Vec<usize> {
-    ids.iter()
-        .filter_map(|id_str| {
-            if id_str.len() == 0 {
-                return None;
+/// Parses a selector made of comma separated parts: ids (`3`), ranges (`1-5`), `all`,
+/// `done` (completed tasks) and negations (`^3`, `^1-2`) removing tasks from the selection.
+/// A selector made only of negations starts from all the tasks.
+pub fn parse_selector(selector: &str, tasks: &[Task]) -> Result<Vec<usize>, Error> {
+    let mut included: Vec<usize> = vec![];
+    let mut excluded: Vec<usize> = vec![];
+    let mut has_inclusion = false;
+
+    for part in parse_args(selector) {
+        let part = part.trim();
+        if part.is_empty() {
+            continue;
+        }
+
+        match part.strip_prefix('^') {
+            Some(negated) => excluded.extend(parse_selector_part(negated, tasks)?),
+            None => {
+                has_inclusion = true;
+                included.extend(parse_selector_part(part, tasks)?);
             }
+        }
+    }
+
+    Ok(tasks
+        .iter()
+        .map(|task| task.id)
+        .filter(|id| !has_inclusion || included.contains(id))
+        .filter(|id| !excluded.contains(id))
+        .collect())
+}
 
-            let parsed = id_str.parse();
-            match parsed {
-                Err(_) => {
-                    println!("You can only use Ids, this is not: {id_str}");
-                    None
+fn parse_selector_part(part: &str, tasks: &[Task]) -> Result<Vec<usize>, Error> {
+    let parse_id = |id_str: &str| -> Result<usize, Error> {
+        let id: usize = id_str.trim().parse().map_err(|_| {
+            Error::invalid(format!(
+                "Invalid selector: {part}, use ids (1,3), ranges (1-5), all, done or ^id"
+            ))
+        })?;
+
+        if !tasks.iter().any(|task| task.id == id) {
+            return Err(Error::not_found(format!(
+                "No task found with this ID: {id} ({} tasks in the context)",
+                tasks.len()
+            )));
+        }
+
+        Ok(id)
+    };
+
+    match part.to_lowercase().as_str() {
+        "all" => Ok(tasks.iter().map(|task| task.id).collect()),
+        "done" => Ok(tasks.iter().filter(|t| t.done).map(|t| t.id).collect()),
+        _ => match part.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (parse_id(start)?, parse_id(end)?);
+                if start > end {
+                    return Err(Error::invalid(format!(
+                        "Invalid range: {part}, the start is after the end"
+                    )));
                 }
-                Ok(id) => Some(id),
+
+                Ok((start..=end).collect())
             }
-        })
+            None => Ok(vec![parse_id(part)?]),
+        },
+    }
+}
+
+/// Returns the ids of the tasks matching the selector (all tasks when empty) and the filter,
+/// failing when none does.
+pub fn select_ids(
+    ctx: &Context,
+    selector: &str,
+    filter: Option<&Filter>,
+) -> Result<Vec<usize>, Error> {
+    if selector.trim().is_empty() && filter.is_none() {
+        return Err(Error::invalid("No task selected"));
+    }
+
+    let ids = parse_selector(selector, &ctx.tasks)?;
+    let selected: Vec<usize> = ctx
+        .tasks
+        .iter()
+        .filter(|task| ids.contains(&task.id))
+        .filter(|task| filter.is_none_or(|filter| filter.matches(task, ctx)))
+        .map(|task| task.id)
+        .collect();
+
+    if selected.is_empty() {
+        return Err(Error::not_found("No tasks matching the selection"));
+    }
+
+    Ok(selected)
+}
+
+/// Parses a date from `YYYY-MM-DD`, `today`, `tomorrow`, `yesterday` or a weekday
+/// (its next occurrence, today included).
+pub fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    let today = Local::now().date_naive();
+
+    let weekday = match value.to_lowercase().as_str() {
+        "today" => return Ok(today),
+        "tomorrow" => return Ok(today + Days::new(1)),
+        "yesterday" => return Ok(today - Days::new(1)),
+        day => day.parse::<Weekday>().ok(),
+    };
+
+    if let Some(weekday) = weekday {
+        let days =
+            (7 + weekday.num_days_from_monday() - today.weekday().num_days_from_monday()) % 7;
+        return Ok(today + Days::new(days.into()));
+    }
+
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date: {value}, use YYYY-MM-DD, today or a weekday"))
+}
+
+/// Extracts the day of a stored date (`YYYY-MM-DD` followed by anything).
+pub fn task_date(date: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(date.get(..10)?, "%Y-%m-%d").ok()
+}
+
+/// Prints the tasks of the given contexts matching the filter, sorted and grouped in sections.
+pub fn print_list(config: &Config, contexts: &[Context], options: &ListOptions) {
+    if config.format != Format::Table {
+        print_task_records(config.format, contexts, options);
+        return;
+    }
+
+    for ctx in contexts {
+        let mut tasks: Vec<Task> = ctx
+            .tasks
+            .iter()
+            .filter(|task| options.filter.as_ref().is_none_or(|f| f.matches(task, ctx)))
+            .cloned()
+            .collect();
+
+        if tasks.is_empty() && options.filter.is_some() && contexts.len() > 1 {
+            continue;
+        }
+
+        if let Some(sort) = options.sort {
+            sort_tasks(&mut tasks, sort);
+        }
+
+        for (title, tasks) in group_tasks(&ctx.name, tasks, options.group_by) {
+            let section = Context {
+                name: title,
+                tasks,
+                ..ctx.clone()
+            };
+            print_tasks_columns(config, &section, &options.columns, &ctx.name);
+        }
+    }
+}
+
+/// Stable sort, tasks with equal keys keep their manual order and missing values go last.
+pub fn sort_tasks(tasks: &mut [Task], sort: Sort) {
+    fn optional<T: Ord>(a: Option<T>, b: Option<T>, desc: bool) -> Ordering {
+        match (a, b) {
+            (Some(a), Some(b)) if desc => b.cmp(&a),
+            (Some(a), Some(b)) => a.cmp(&b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    }
+
+    tasks.sort_by(|a, b| {
+        let ordering = match sort.key {
+            SortKey::Id => a.id.cmp(&b.id),
+            SortKey::Created => a.creation_date.cmp(&b.creation_date),
+            SortKey::Modified => a.modification_date.cmp(&b.modification_date),
+            SortKey::Status => a.done.cmp(&b.done),
+            SortKey::Content => a.content.to_lowercase().cmp(&b.content.to_lowercase()),
+            SortKey::Due => return optional(a.due.as_ref(), b.due.as_ref(), sort.desc),
+            SortKey::Priority => return optional(a.priority, b.priority, sort.desc),
+        };
+
+        if sort.desc {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+}
+
+fn group_tasks(
+    name: &str,
+    tasks: Vec<Task>,
+    group_by: Option<GroupBy>,
+) -> Vec<(String, Vec<Task>)> {
+    match group_by {
+        None | Some(GroupBy::Context) => vec![(name.to_owned(), tasks)],
+        Some(GroupBy::Status) if tasks.is_empty() => vec![(name.to_owned(), tasks)],
+        Some(GroupBy::Status) => {
+            let (done, open): (Vec<Task>, Vec<Task>) = tasks.into_iter().partition(|t| t.done);
+            [("open", open), ("done", done)]
+                .into_iter()
+                .filter(|(_, tasks)| !tasks.is_empty())
+                .map(|(status, tasks)| (format!("{name} · {status}"), tasks))
+                .collect()
+        }
+        Some(GroupBy::Tag) => {
+            let mut tags: Vec<&String> = tasks.iter().flat_map(|task| &task.tags).collect();
+            tags.sort();
+            tags.dedup();
+
+            let mut sections: Vec<(String, Vec<Task>)> = tags
+                .into_iter()
+                .map(|tag| {
+                    let tagged = tasks.iter().filter(|t| t.tags.contains(tag)).cloned();
+                    (format!("{name} · +{tag}"), tagged.collect())
+                })
+                .collect();
+
+            let untagged: Vec<Task> = tasks
+                .iter()
+                .filter(|t| t.tags.is_empty())
+                .cloned()
+                .collect();
+            if !untagged.is_empty() || sections.is_empty() {
+                sections.push((format!("{name} · no tag"), untagged));
+            }
+
+            sections
+        }
+    }
+}
+
+pub fn build_search_regex(pattern: &str, regex: bool) -> Result<Regex, String> {
+    let pattern = if regex {
+        pattern.to_owned()
+    } else {
+        regex::escape(pattern)
+    };
+
+    RegexBuilder::new(&pattern)
+        .case_insensitive(true)
+        .build()
+        .map_err(|err| format!("Invalid search pattern: {err}"))
+}
+
+/// Prints the tasks matching the search grouped by context, with the matches highlighted.
+pub fn print_search(config: &Config, contexts: &[Context], search: &Regex) {
+    if config.format != Format::Table {
+        print_search_records(config.format, contexts, search);
+        return;
+    }
+
+    let highlight = std::io::stdout().is_terminal();
+    let mut count = 0;
+
+    for ctx in contexts {
+        // The API numbers the tasks across contexts, their position is the id to pass to commands
+        let matches: Vec<Task> = positioned(ctx)
+            .filter(|task| search.is_match(&task.content))
+            .collect();
+
+        if matches.is_empty() {
+            continue;
+        }
+
+        println!("{}", ctx.name);
+        for task in matches {
+            let check = if task.done { "[X]" } else { "[]" };
+            let content = if highlight {
+                search.replace_all(&task.content, "\x1b[1;33m$0\x1b[0m")
+            } else {
+                task.content.as_str().into()
+            };
+
+            println!("  {:>3} {:<3} {}", task.id, check, content);
+            count += 1;
+        }
+    }
+
+    match count {
+        0 => println!("No tasks found"),
+        1 => println!("1 task found"),
+        _ => println!("{count} tasks found"),
+    }
+}
+
+/// Prints the message of a command changing the data, unless --quiet is given.
+pub fn print_message(config: &Config, message: impl Display) {
+    if !config.quiet {
+        println!("{message}");
+    }
+}
+
+/// Runs a service call and returns the lines it printed instead of showing them.
+pub fn capture_output(action: impl FnOnce()) -> Vec<String> {
+    capture(BufferRedirect::stdout(), action)
+}
+
+/// Runs the action, returning the errors it printed on stderr.
+pub fn capture_errors(action: impl FnOnce()) -> Vec<String> {
+    capture(BufferRedirect::stderr(), action)
+}
+
+fn capture(redirect: std::io::Result<BufferRedirect>, action: impl FnOnce()) -> Vec<String> {
+    let Ok(mut redirect) = redirect else {
+        action();
+        return vec![];
+    };
+
+    action();
+    let _ = std::io::stdout().flush();
+    let _ = std::io::stderr().flush();
+
+    let mut output = String::new();
+    let _ = redirect.read_to_string(&mut output);
+
+    output
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.to_owned())
         .collect()
 }
 
+pub fn print_reports(config: &Config) {
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS);
+
+    let mut names: Vec<&String> = config.reports.keys().collect();
+    names.sort();
+
+    for name in names {
+        let report = &config.reports[name];
+        table.add_row(vec![
+            name.to_owned(),
+            report.description.to_owned(),
+            report.filter.to_owned(),
+        ]);
+    }
+
+    if config.reports.is_empty() {
+        table.add_row(vec![
+            "Add your first report under [reports.{name}] in the config",
+        ]);
+    }
+
+    println!("{table}");
+}
+
+/// Moves a task to a position (clamped to the list bounds), ids follow the new order.
+pub fn reorder_tasks(tasks: &mut Vec<Task>, id: usize, position: usize) -> Result<(), Error> {
+    let index = tasks
+        .iter()
+        .position(|task| task.id == id)
+        .ok_or(Error::not_found(format!(
+            "No task found with this ID: {id}"
+        )))?;
+
+    let task = tasks.remove(index);
+    let position = position.clamp(1, tasks.len() + 1);
+    tasks.insert(position - 1, task);
+
+    let date = Local::now().to_string();
+    for (i, task) in tasks.iter_mut().enumerate() {
+        if task.id != i + 1 {
+            task.id = i + 1;
+            task.modification_date = date.clone();
+        }
+    }
+
+    Ok(())
+}
+
+/// Finds the index of a context from its name or its id, names take precedence.
+pub fn find_context(data: &[Context], name_or_id: &str) -> Option<usize> {
+    data.iter()
+        .position(|ctx| ctx.name == name_or_id)
+        .or_else(|| {
+            let id: usize = name_or_id.parse().ok()?;
+            data.iter().position(|ctx| ctx.id == id)
+        })
+}
+
+pub fn next_context_id(data: &[Context]) -> usize {
+    data.iter().map(|ctx| ctx.id).max().unwrap_or(0) + 1
+}
+
+/// Gives a new id to contexts sharing an id with a previous one (or having none),
+/// returning whether any id changed.
+pub fn repair_context_ids(contexts: &mut [Context]) -> bool {
+    let mut seen = HashSet::new();
+    let mut next_id = next_context_id(contexts);
+    let mut repaired = false;
+
+    for ctx in contexts.iter_mut() {
+        if ctx.id == 0 || !seen.insert(ctx.id) {
+            eprintln!(
+                "Context {} had a duplicate ID: {}, its new ID is {}",
+                ctx.name, ctx.id, next_id
+            );
+            ctx.id = next_id;
+            seen.insert(next_id);
+            next_id += 1;
+            repaired = true;
+        }
+    }
+
+    repaired
+}
+
+/// Returns the index of the context targeted by the command,
+/// the one passed with `--context` or the active one by default.
+pub fn get_context_index(data: &[Context], config: &Config) -> Result<usize, Error> {
+    match &config.context {
+        Some(name_or_id) => find_context(data, name_or_id).ok_or(Error::not_found(format!(
+            "No context found with this name or ID: {name_or_id}"
+        ))),
+        None => data
+            .iter()
+            .position(|ctx| ctx.active)
+            .ok_or(Error::not_found(
+                "No current active context, let's create one using task use {name}",
+            )),
+    }
+}
+
+/// Parses a color name (red, dark_blue...) or a hex code (#ff8800).
+pub fn parse_color(color: &str) -> Option<Color> {
+    if let Some(hex) = color.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        return Some(Color::Rgb {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+        });
+    }
+
+    let color = match color.to_lowercase().replace('-', "_").as_str() {
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "dark_red" => Color::DarkRed,
+        "green" => Color::Green,
+        "dark_green" => Color::DarkGreen,
+        "yellow" => Color::Yellow,
+        "dark_yellow" => Color::DarkYellow,
+        "blue" => Color::Blue,
+        "dark_blue" => Color::DarkBlue,
+        "magenta" => Color::Magenta,
+        "dark_magenta" => Color::DarkMagenta,
+        "cyan" => Color::Cyan,
+        "dark_cyan" => Color::DarkCyan,
+        "white" => Color::White,
+        "grey" | "gray" => Color::Grey,
+        "dark_grey" | "dark_gray" => Color::DarkGrey,
+        _ => return None,
+    };
+
+    Some(color)
+}
+
+pub fn format_task(task: &Task) -> String {
+    let mut line = task.content.to_owned();
+
+    if let Some(priority) = task.priority {
+        line.push_str(&format!(" ({priority})"));
+    }
+
+    for tag in &task.tags {
+        line.push_str(&format!(" +{tag}"));
+    }
+
+    if let Some(due) = &task.due {
+        line.push_str(&format!(" due:{due}"));
+    }
+
+    line
+}
+
 pub fn print_tasks(config: &Config, ctx: &Context) {
+    print_tasks_columns(config, ctx, &[], &ctx.name);
+}
+
+/// Prints the tasks with the given columns, the content one having the context name as header.
+/// Without columns, priority, tags and due date are shown next to the content.
+fn print_tasks_columns(config: &Config, ctx: &Context, columns: &[Column], ctx_name: &str) {
+    if columns.is_empty() {
+        return print_default_tasks(config, ctx);
+    }
+
     let mut table = Table::new();
 
     table
         .load_preset(UTF8_FULL)
         .apply_modifier(UTF8_ROUND_CORNERS);
 
-    table.set_header(vec![
-        Cell::new(""),
-        Cell::new(""),
-        Cell::new(&break_line(ctx.name.to_owned(), &config.max_line_lengh)),
-    ]);
+    let title_index = columns
+        .iter()
+        .position(|column| *column == Column::Content)
+        .unwrap_or(0);
+
+    let header: Vec<Cell> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            if i == title_index {
+                let cell = Cell::new(break_line(ctx.name.to_owned(), &config.max_line_lengh));
+                return match parse_color(&ctx.meta.color) {
+                    Some(color) => cell.fg(color),
+                    None => cell,
+                };
+            }
+
+            match column {
+                Column::Id | Column::Status => Cell::new(""),
+                column => Cell::new(format!("{:?}", column).to_lowercase()),
+            }
+        })
+        .collect();
+    table.set_header(header);
+
+    for task in &ctx.tasks {
+        let row: Vec<Cell> = columns
+            .iter()
+            .map(|column| {
+                let value = match column {
+                    Column::Id => task.id.to_string(),
+                    Column::Status => String::from(if task.done { "[X]" } else { "[]" }),
+                    Column::Content => break_line(task.content.to_owned(), &config.max_line_lengh),
+                    Column::Priority => task.priority.map(|p| p.to_string()).unwrap_or_default(),
+                    Column::Tags => task
+                        .tags
+                        .iter()
+                        .map(|tag| format!("+{tag}"))
+                        .collect::<Vec<String>>()
+                        .join(" "),
+                    Column::Due => task.due.to_owned().unwrap_or_default(),
+                    Column::Created => task.creation_date.chars().take(16).collect(),
+                    Column::Modified => task.modification_date.chars().take(16).collect(),
+                    Column::Context => ctx_name.to_owned(),
+                };
+
+                Cell::new(value)
+            })
+            .collect();
+
+        table.add_row(row);
+    }
+
+    if ctx.tasks.is_empty() {
+        let row: Vec<Cell> = (0..columns.len())
+            .map(|i| match i == title_index {
+                true => Cell::new("No tasks, are you lazy or too efficient?"),
+                false => Cell::new(""),
+            })
+            .collect();
+        table.add_row(row);
+    }
+
+    println!("{table}");
+}
+
+fn print_default_tasks(config: &Config, ctx: &Context) {
+    let mut table = Table::new();
+
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS);
+
+    let mut header = Cell::new(break_line(ctx.name.to_owned(), &config.max_line_lengh));
+    if let Some(color) = parse_color(&ctx.meta.color) {
+        header = header.fg(color);
+    }
+
+    table.set_header(vec![Cell::new(""), Cell::new(""), header]);
 
     for task in &ctx.tasks {
         let check = if task.done {
@@ -52,7 +607,7 @@ pub fn print_tasks(config: &Config, ctx: &Context) {
             "[]".to_string()
         };
 
-        let splitted_line = break_line(task.content.to_owned(), &config.max_line_lengh);
+        let splitted_line = break_line(format_task(task), &config.max_line_lengh);
 
         table.add_row(vec![
             Cell::new(task.id.to_owned()),
@@ -61,7 +616,7 @@ pub fn print_tasks(config: &Config, ctx: &Context) {
         ]);
     }
 
-    if ctx.tasks.len() == 0 {
+    if ctx.tasks.is_empty() {
         table.add_row(vec![
             Cell::new(""),
             Cell::new(""),
@@ -76,106 +631,110 @@ pub fn print_tasks(config: &Config, ctx: &Context) {
 }
 
 pub fn get_or_create_data_file(
-    file: &String,
-    folder: &String,
+    file: &str,
+    folder: &str,
     create_file: bool,
-) -> Result<Vec<Context>, String> {
-    let folder_path = Path::new(folder.as_str());
-    let file_path = Path::new(file.as_str());
+) -> Result<Vec<Context>, Error> {
+    let folder_path = Path::new(folder);
+    let file_path = Path::new(file);
 
     if !create_file && (!folder_path.exists() || !file_path.is_file()) {
-        return Err(format!("No data file found: {file}"));
+        return Err(Error::storage(format!("No data file found: {file}")));
     }
 
     if !folder_path.exists() {
-        create_dir_all(folder_path).expect("Error when creating folder tasks");
+        create_dir_all(folder_path)
+            .map_err(|err| Error::storage(format!("Error when creating folder {folder}: {err}")))?;
     }
 
     if !file_path.is_file() {
-        let mut file = File::create(file_path).expect("Error when creating file tasks.json");
-        file.write("[]".as_bytes())
-            .expect("Error when writing to file");
+        File::create(file_path)
+            .and_then(|mut created| created.write_all("[]".as_bytes()))
+            .map_err(|err| Error::storage(format!("Error when creating file {file}: {err}")))?;
 
         return Ok(Vec::new());
     };
 
-    let file = File::open(file_path).expect("Error when opening file");
-    let reader = BufReader::new(file);
-    let contexts: Vec<Context> =
-        serde_json::from_reader(reader).expect("Error when extracting data from file");
-
-    Ok(contexts)
+    let opened = File::open(file_path)
+        .map_err(|err| Error::storage(format!("Error when opening file {file}: {err}")))?;
+    read_contexts(BufReader::new(opened), file)
 }
 
 pub fn get_or_create_data_file_ssh(
     config: &Config,
     create_file: bool,
-) -> Result<Vec<Context>, String> {
-    let sftp_res = get_sftp(&config);
-    if sftp_res.is_err() {
-        return Err(String::from("Error when getting SFTP connection"));
-    };
-
-    let sftp = sftp_res.unwrap();
-    let path_str = get_remote_path(&config);
+) -> Result<Vec<Context>, Error> {
+    let sftp = get_sftp(config)?;
+    let path_str = get_remote_path(config);
     let path = Path::new(&path_str);
-    let file_res = sftp.open(path);
-
-    match file_res {
-        Ok(file) => {
-            let reader = BufReader::new(file);
-            let contexts: Vec<Context> =
-                serde_json::from_reader(reader).expect("Error when extracting data from file");
+    let ip = &config.ssh_ip;
 
-            Ok(contexts)
-        }
+    match sftp.open(path) {
+        Ok(file) => read_contexts(BufReader::new(file), &path_str),
         Err(_) => {
-            let ip = &config.ssh_ip;
             if !create_file {
-                return Err(format!("No file found on remote server: {ip}:{path_str}"));
+                return Err(Error::storage(format!(
+                    "No file found on remote server: {ip}:{path_str}"
+                )));
             }
 
-            let mut file = sftp.create(path).expect("Error when creating file");
-            file.write("[]".as_bytes())
-                .expect("Error when writing to file");
-
-            file.close().unwrap();
+            let error = |err: &dyn std::error::Error| {
+                Error::storage(format!("Error when creating file {ip}:{path_str}: {err}"))
+            };
+            let mut file = sftp.create(path).map_err(|err| error(&err))?;
+            file.write_all("[]".as_bytes()).map_err(|err| error(&err))?;
+            file.close().map_err(|err| error(&err))?;
 
             Ok(vec![])
         }
     }
 }
 
-pub fn get_sftp(config: &Config) -> Result<Sftp, ()> {
+fn read_contexts(reader: impl Read, file: &str) -> Result<Vec<Context>, Error> {
+    serde_json::from_reader(reader)
+        .map_err(|err| Error::storage(format!("Error when extracting data from {file}: {err}")))
+}
+
+pub fn get_sftp(config: &Config) -> Result<Sftp, Error> {
+    let ip = &config.ssh_ip;
+    let error = |action: &str, err: &dyn std::error::Error| {
+        Error::storage(format!("Error when {action} {ip}: {err}"))
+    };
+
     // Connect to the local SSH server
-    let tcp = TcpStream::connect(&config.ssh_ip).expect("TCP connection failed");
-    let mut sess = Session::new().expect("Error while creating TCP Session");
+    let tcp = TcpStream::connect(ip).map_err(|err| error("connecting to", &err))?;
+    let mut sess = Session::new().map_err(|err| error("opening an SSH session with", &err))?;
 
     sess.set_tcp_stream(tcp);
-    sess.handshake().expect("Error with the TCP connection");
+    sess.handshake()
+        .map_err(|err| error("opening an SSH session with", &err))?;
 
     // Try to authenticate with the first identity in the agent.
-    sess.userauth_agent(&config.ssh_username)
-        .expect("Error when setting user agent, you might need to add ssh key to ssh-agent");
+    sess.userauth_agent(&config.ssh_username).map_err(|err| {
+        Error::storage(format!(
+            "Error when authenticating on {ip}: {err}, you might need to add ssh key to ssh-agent"
+        ))
+    })?;
     if !sess.authenticated() {
-        println!("Authentication failed");
-        return Err(());
+        return Err(Error::storage(format!("Authentication failed on {ip}")));
     }
 
-    let sftp = sess.sftp().expect("Sftp subsystem initialization failed");
-
-    Ok(sftp)
+    sess.sftp()
+        .map_err(|err| error("opening the SFTP subsystem of", &err))
 }
 
 pub fn get_remote_path(config: &Config) -> String {
+    get_remote_file(config, "tasks.json")
+}
+
+/// Path of a file stored next to the remote data file.
+pub fn get_remote_file(config: &Config, name: &str) -> String {
     let sep = if config.ssh_file_path.is_empty() {
         ""
     } else {
         "/"
     };
-    let file_path = format!("{}{sep}tasks.json", config.ssh_file_path);
-
-    file_path
+    format!("{}{sep}{name}", config.ssh_file_path)
 }
 
 fn break_line(line: String, max_line_length: &usize) -> String {
@@ -204,3 +763,87 @@ fn break_line(line: String, max_line_length: &usize) -> String {
 
     formatted
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::TaskError;
+
+    /// A context of five tasks, the second and fourth ones done.
+    fn context() -> Context {
+        let mut ctx = Context::new("work", 1);
+        ctx.tasks = (1..=5)
+            .map(|id| Task {
+                id,
+                content: format!("Task {id}"),
+                done: id % 2 == 0,
+                creation_date: String::from("2026-10-10 09:00:00.000000000 +00:00"),
+                modification_date: String::from("2026-10-10 09:00:00.000000000 +00:00"),
+                priority: None,
+                tags: vec![],
+                due: None,
+            })
+            .collect();
+        ctx
+    }
+
+    fn select(selector: &str) -> Result<Vec<usize>, Error> {
+        parse_selector(selector, &context().tasks)
+    }
+
+    #[test]
+    fn selectors_combine_ids_ranges_and_keywords() {
+        assert_eq!(select("3"), Ok(vec![3]));
+        assert_eq!(select("4,1-2"), Ok(vec![1, 2, 4]));
+        assert_eq!(select("all"), Ok(vec![1, 2, 3, 4, 5]));
+        assert_eq!(select("done"), Ok(vec![2, 4]));
+        assert_eq!(select("DONE,5"), Ok(vec![2, 4, 5]));
+    }
+
+    #[test]
+    fn negations_remove_tasks_from_the_selection() {
+        assert_eq!(select("all,^2-3"), Ok(vec![1, 4, 5]));
+        assert_eq!(select("^1"), Ok(vec![2, 3, 4, 5]));
+        assert_eq!(select("^done"), Ok(vec![1, 3, 5]));
+        assert_eq!(select("1-3,^done"), Ok(vec![1, 3]));
+    }
+
+    #[test]
+    fn invalid_selectors_are_errors() {
+        assert_eq!(
+            select("6"),
+            Err(Error::not_found(
+                "No task found with this ID: 6 (5 tasks in the context)"
+            ))
+        );
+        assert_eq!(select("2-9").unwrap_err().kind, TaskError::NotFound,);
+        assert_eq!(
+            select("4-2"),
+            Err(Error::invalid(
+                "Invalid range: 4-2, the start is after the end"
+            ))
+        );
+        assert_eq!(select("two").unwrap_err().kind, TaskError::InvalidInput);
+    }
+
+    #[test]
+    fn select_ids_applies_the_filter_and_fails_when_empty() {
+        let ctx = context();
+        let open = Filter::parse("status:open").unwrap();
+
+        assert_eq!(select_ids(&ctx, "1-4", Some(&open)), Ok(vec![1, 3]));
+        assert_eq!(select_ids(&ctx, "", Some(&open)), Ok(vec![1, 3, 5]));
+        assert_eq!(
+            select_ids(&ctx, "2,4", Some(&open)),
+            Err(Error::not_found("No tasks matching the selection"))
+        );
+        assert_eq!(
+            select_ids(&ctx, "^all", None),
+            Err(Error::not_found("No tasks matching the selection"))
+        );
+        assert_eq!(
+            select_ids(&ctx, " ", None),
+            Err(Error::invalid("No task selected"))
+        );
+    }
+}
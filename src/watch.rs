@@ -0,0 +1,129 @@
+use chrono::Local;
+use notify::{recommended_watcher, Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::errors::{report_as, TaskError};
+use crate::output::Format;
+use crate::structs::{get_terminal_width, Config, ListOptions, Service};
+
+/// How often the terminal size is checked
+const TICK: Duration = Duration::from_millis(250);
+
+/// Redraws the list when the data changes, until interrupted: on file notifications for a
+/// local data file, polling the SSH file or the API every `watch_interval` seconds otherwise.
+pub fn watch(config: &Config, service: &impl Service, options: ListOptions) {
+    let local = config.api_url.is_empty() && config.ssh_ip.is_empty();
+    let (sender, receiver) = mpsc::channel();
+    let _watcher = match local {
+        true => match watch_file(config, sender) {
+            Ok(watcher) => Some(watcher),
+            Err(err) => {
+                report_as(
+                    TaskError::Storage,
+                    format!("Error when watching {}: {}", config.local_file_path, err),
+                );
+                return;
+            }
+        },
+        false => None,
+    };
+
+    let interval = Duration::from_secs(config.watch_interval.max(1));
+    let mut config = config.clone();
+    let mut snapshot = None;
+    let mut last_poll: Option<Instant> = None;
+
+    loop {
+        let check = match local {
+            true => snapshot.is_none() || data_changed(&receiver),
+            false => last_poll.is_none_or(|last| last.elapsed() >= interval),
+        };
+
+        let mut redraw = false;
+        if check {
+            last_poll = Some(Instant::now());
+            let current = Some(fingerprint(&config, service));
+            redraw = current != snapshot;
+            snapshot = current;
+        }
+
+        let width = get_terminal_width();
+        if width != config.max_line_lengh {
+            config.max_line_lengh = width;
+            redraw = true;
+        }
+
+        if redraw {
+            draw(&config, service, &options, local);
+        }
+
+        if !local {
+            thread::sleep(TICK);
+        }
+    }
+}
+
+fn watch_file(
+    config: &Config,
+    sender: mpsc::Sender<notify::Result<Event>>,
+) -> notify::Result<RecommendedWatcher> {
+    let mut watcher = recommended_watcher(sender)?;
+    // The folder is watched as the file may be replaced rather than written to
+    watcher.watch(Path::new(&config.folder_path), RecursiveMode::NonRecursive)?;
+
+    Ok(watcher)
+}
+
+/// Waits a tick for a change of the data file, reading the following notifications of the same write.
+fn data_changed(receiver: &Receiver<notify::Result<Event>>) -> bool {
+    let is_data = |event: notify::Result<Event>| {
+        event.is_ok_and(|event| {
+            !event.kind.is_access()
+                && event
+                    .paths
+                    .iter()
+                    .any(|path| path.file_name().is_some_and(|name| name == "tasks.json"))
+        })
+    };
+
+    let mut changed = match receiver.recv_timeout(TICK) {
+        Ok(event) => is_data(event),
+        Err(RecvTimeoutError::Timeout) => false,
+        Err(RecvTimeoutError::Disconnected) => return false,
+    };
+    while let Ok(event) = receiver.recv_timeout(Duration::from_millis(50)) {
+        changed |= is_data(event);
+    }
+
+    changed
+}
+
+/// The data as text, or the error, to only redraw when one of them changes.
+fn fingerprint(config: &Config, service: &impl Service) -> String {
+    match service.get_contexts(config) {
+        Ok(contexts) => serde_json::to_string(&contexts).unwrap_or_default(),
+        Err(err) => err.message,
+    }
+}
+
+fn draw(config: &Config, service: &impl Service, options: &ListOptions, local: bool) {
+    let source = match local {
+        true => String::from("on change"),
+        false => format!("every {}s", config.watch_interval.max(1)),
+    };
+
+    // Only a table on a terminal is redrawn, other formats and pipes get each listing in turn
+    if config.format == Format::Table && io::stdout().is_terminal() {
+        // Clears the screen and moves the cursor to the top
+        print!("\x1b[2J\x1b[H");
+        println!(
+            "Refreshed {source}, last at {} (Ctrl+C to quit)",
+            Local::now().format("%H:%M:%S")
+        );
+    }
+    service.list_tasks(config, options.clone());
+}